@@ -0,0 +1,67 @@
+/*
+ * Labels the voice selections of an SMF with the names of the voices.
+ */
+
+use std::collections::BTreeSet;
+
+use super::midi::*;
+use super::models::{Model, Resolution};
+use super::smf::{Event, Smf, Track, TrackEvent, META_MARKER, META_TRACK_NAME};
+
+// The voice that `sel` plays on `model`, as in "000-041-001 Dream".
+pub fn describe(model: &Model, sel: Selection) -> String {
+	match model.resolve(sel.msb, sel.lsb, sel.prg) {
+		Resolution::Exact(voice) => voice.to_string().trim_end().to_string(),
+		Resolution::Fallback(voice) => format!("{} -> {}", sel, voice.to_string().trim_end()),
+		Resolution::Missing => format!("{} (not on {})", sel, model.name),
+	}
+}
+
+fn meta(tick: u64, kind: u8, text: &str) -> TrackEvent {
+	TrackEvent { tick, event: Event::Meta { kind, data: text.as_bytes().to_vec() } }
+}
+
+// Inserts a marker naming the voice before every Program Change, and names
+// tracks whose notes all play the same voice after it. Existing track names
+// of those tracks are replaced, everything else is left as is.
+pub fn annotate(smf: &Smf, model: &Model) -> Smf {
+	let mut tracks: Vec<Vec<TrackEvent>> = vec![Vec::new(); smf.tracks.len()];
+	// Voices heard on each track.
+	let mut voices: Vec<BTreeSet<Selection>> = vec![BTreeSet::new(); smf.tracks.len()];
+	for sequence in smf.sequences() {
+		let mut channels: Channels = Default::default();
+		for (track, e) in sequence {
+			match e.event {
+				Event::Channel { channel, ref msg } => {
+					if let Some(Change::Selected(sel)) = channels.update(channel, msg) {
+						let text = format!("Ch {}: {}", channel + 1, describe(model, sel));
+						tracks[track].push(meta(e.tick, META_MARKER, &text));
+					}
+					// Only voices that actually play notes count towards the
+					// track name.
+					if let ChannelMsg::NoteOn { vel, .. } = *msg {
+						if vel > 0 {
+							voices[track].insert(channels.0[channel as usize].selection);
+						}
+					}
+				}
+				Event::SysEx(ref data) if is_reset(data) => channels.reset(),
+				_ => {}
+			}
+			tracks[track].push(e.clone());
+		}
+	}
+	for (events, voices) in tracks.iter_mut().zip(&voices) {
+		if voices.len() != 1 {
+			continue;
+		}
+		let name = describe(model, *voices.iter().next().unwrap());
+		events.retain(|e| !matches!(e.event, Event::Meta { kind: META_TRACK_NAME, .. }));
+		events.insert(0, meta(0, META_TRACK_NAME, &name));
+	}
+	Smf {
+		format: smf.format,
+		division: smf.division,
+		tracks: tracks.into_iter().map(|events| Track { events }).collect(),
+	}
+}
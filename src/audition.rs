@@ -0,0 +1,194 @@
+/*
+ * Generates SMFs that play every voice of a model, for checking a module
+ * by ear.
+ */
+
+use std::ops::RangeInclusive;
+
+use super::drums;
+use super::midi::*;
+use super::models::Model;
+use super::smf::{Division, Event, Smf, Track, TrackEvent, META_MARKER, META_TEMPO};
+use super::sysex;
+use super::voices::*;
+
+const TICKS_PER_QUARTER: u16 = 480;
+const QUARTER: u64 = TICKS_PER_QUARTER as u64;
+const BAR: u64 = 4 * QUARTER;
+
+// 120 BPM, in microseconds per quarter note.
+const TEMPO: u32 = 500_000;
+
+const VELOCITY: u8 = 100;
+
+pub struct Options {
+	// MSB and LSB.
+	pub bank: Option<(u8, u8)>,
+	// 1-based.
+	pub programs: RangeInclusive<u8>,
+	pub kind: Option<VoiceKind>,
+}
+
+impl Default for Options {
+	fn default() -> Options {
+		Options { bank: None, programs: 1..=128, kind: None }
+	}
+}
+
+impl Options {
+	pub fn matches(&self, voice: &Voice) -> bool {
+		self.bank.is_none_or(|bank| bank == (voice.msb, voice.lsb))
+			&& self.programs.contains(&voice.prg)
+			&& self.kind.is_none_or(|kind| kind == voice.kind())
+	}
+}
+
+// A note relative to the start of a voice's phrase.
+struct Note {
+	start: u64,
+	length: u64,
+	key: u8,
+}
+
+fn note(start: u64, length: u64, key: u8) -> Note {
+	Note { start, length, key }
+}
+
+// Keys of the General MIDI drum map.
+const KICK: u8 = 36;
+const SNARE: u8 = 38;
+const CLOSED_HIHAT: u8 = 42;
+
+// Two bars of something that suits the voice.
+fn phrase(voice: &Voice) -> Vec<Note> {
+	match voice.kind() {
+		VoiceKind::Kit => (0..16)
+			.flat_map(|i| {
+				let start = i * QUARTER / 2;
+				let mut ret = vec![note(start, QUARTER / 2, CLOSED_HIHAT)];
+				match i % 4 {
+					0 => ret.push(note(start, QUARTER / 2, KICK)),
+					2 => ret.push(note(start, QUARTER / 2, SNARE)),
+					_ => {}
+				}
+				ret
+			})
+			.collect(),
+		// Single hits, long enough for sounds that take a while to unfold.
+		VoiceKind::Sfx => vec![note(0, BAR - QUARTER, 60), note(BAR, BAR - QUARTER, 60)],
+		VoiceKind::Instrument => match gm_category(voice.prg) {
+			"Synth Pad" | "Strings" | "Ensemble" => {
+				[48, 60, 64, 67].iter().map(|&key| note(0, 2 * BAR - QUARTER, key)).collect()
+			}
+			category => {
+				let base = if category == "Bass" { 36 } else { 60 };
+				[0, 4, 7, 12, 7, 4, 0].iter().enumerate()
+					.map(|(i, &offset)| {
+						let length = if i == 6 { BAR } else { QUARTER / 2 };
+						note(i as u64 * QUARTER / 2, length, base + offset)
+					})
+					.collect()
+			}
+		},
+	}
+}
+
+// Tempo and reset. XG modules need the System On to enable the bank
+// selects.
+fn header(model: &Model) -> Vec<TrackEvent> {
+	let reset = if model.is_xg() { sysex::xg_system_on(0) } else { sysex::gm_system_on() };
+	vec![
+		TrackEvent {
+			tick: 0,
+			event: Event::Meta { kind: META_TEMPO, data: TEMPO.to_be_bytes()[1..].to_vec() },
+		},
+		TrackEvent { tick: 0, event: Event::SysEx(reset) },
+	]
+}
+
+fn marker(tick: u64, text: &str) -> TrackEvent {
+	TrackEvent { tick, event: Event::Meta { kind: META_MARKER, data: text.as_bytes().to_vec() } }
+}
+
+// Marks and selects `voice`, returning the channel it was selected on.
+// Kits are played on the drum channel, everything else on channel 1.
+fn select(events: &mut Vec<TrackEvent>, tick: u64, voice: &Voice) -> u8 {
+	let channel = if voice.kind() == VoiceKind::Kit { DRUM_CHANNEL } else { 0 };
+	events.push(marker(tick, voice.to_string().trim_end()));
+	for &msg in &[
+		ChannelMsg::Control { controller: CC_BANK_MSB, value: voice.msb },
+		ChannelMsg::Control { controller: CC_BANK_LSB, value: voice.lsb },
+		ChannelMsg::Program(voice.prg - 1),
+	] {
+		events.push(TrackEvent { tick, event: Event::Channel { channel, msg } });
+	}
+	channel
+}
+
+// Adds the Note On and Off events of `notes`, starting at `start`.
+fn play(events: &mut Vec<TrackEvent>, start: u64, channel: u8, notes: &[Note]) {
+	// Note offs first, so that they don't cut off repeated notes after the
+	// stable sort in `finish`.
+	for n in notes {
+		let msg = ChannelMsg::NoteOff { key: n.key, vel: 0 };
+		let tick = start + n.start + n.length;
+		events.push(TrackEvent { tick, event: Event::Channel { channel, msg } });
+	}
+	for n in notes {
+		let msg = ChannelMsg::NoteOn { key: n.key, vel: VELOCITY };
+		let tick = start + n.start;
+		events.push(TrackEvent { tick, event: Event::Channel { channel, msg } });
+	}
+}
+
+fn finish(mut events: Vec<TrackEvent>) -> Smf {
+	events.sort_by_key(|e| e.tick);
+	Smf {
+		format: 0,
+		division: Division::TicksPerQuarter(TICKS_PER_QUARTER),
+		tracks: vec![Track { events }],
+	}
+}
+
+// Builds a format 0 SMF that selects each matching voice of `model` in
+// turn, marks it with a marker event and plays a short phrase on it.
+pub fn generate(model: &Model, opts: &Options) -> Smf {
+	let mut events = header(model);
+	let mut tick = QUARTER;
+	for voice in model.voices().filter(|v| opts.matches(v)) {
+		let channel = select(&mut events, tick, voice);
+		// Give the module some time to load the voice.
+		play(&mut events, tick + QUARTER / 4, channel, &phrase(voice));
+		tick += 2 * BAR + 2 * QUARTER;
+	}
+	finish(events)
+}
+
+// Builds a format 0 SMF that plays every note of each matching kit of
+// `model` in turn, with a marker naming each note's instrument. Kits
+// without a known note map play keys 13-88 with unnamed markers.
+pub fn generate_kits(model: &Model, opts: &Options) -> Smf {
+	let xg = model.is_xg();
+	let mut events = header(model);
+	let mut tick = QUARTER;
+	let kits = model.voices().filter(|v| v.kind() == VoiceKind::Kit && opts.matches(v));
+	for kit in kits {
+		let channel = select(&mut events, tick, kit);
+		let notes: Vec<(u8, Option<&str>)> = match drums::note_map(kit, xg) {
+			Some(map) => map.notes().into_iter().map(|(key, name)| (key, Some(name))).collect(),
+			None => (13..=88).map(|key| (key, None)).collect(),
+		};
+		tick += QUARTER;
+		for (key, name) in notes {
+			let text = match name {
+				Some(name) => format!("{:03} {}", key, name),
+				None => format!("{:03}", key),
+			};
+			events.push(marker(tick, &text));
+			play(&mut events, tick, channel, &[note(0, QUARTER / 2, key)]);
+			tick += QUARTER;
+		}
+		tick += BAR;
+	}
+	finish(events)
+}
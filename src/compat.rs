@@ -0,0 +1,69 @@
+/*
+ * Ranks models by how faithfully they can play the voices used in a song.
+ */
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::midi::Selection;
+use super::models::{Model, Resolution};
+use super::smf;
+
+pub struct Compatibility {
+	pub model: &'static Model,
+	pub exact: Vec<Selection>,
+	pub fallback: Vec<Selection>,
+	pub missing: Vec<Selection>,
+}
+
+// All distinct voices selected anywhere in the song, including the default
+// ones of channels that play notes without any Program Change.
+pub fn used_selections(smf: &smf::Smf) -> BTreeSet<Selection> {
+	smf.sequences().iter()
+		.flat_map(|seq| smf::voice_changes(seq))
+		.map(|c| c.change.selection())
+		.collect()
+}
+
+impl Compatibility {
+	pub fn new(model: &'static Model, used: &BTreeSet<Selection>) -> Compatibility {
+		let mut ret = Compatibility {
+			model, exact: Vec::new(), fallback: Vec::new(), missing: Vec::new()
+		};
+		for &sel in used {
+			match model.resolve(sel.msb, sel.lsb, sel.prg) {
+				Resolution::Exact(_) => ret.exact.push(sel),
+				Resolution::Fallback(_) => ret.fallback.push(sel),
+				Resolution::Missing => ret.missing.push(sel),
+			}
+		}
+		ret
+	}
+}
+
+// Sorts models from most to least compatible. Models with the same score
+// keep their order, so that the oldest sufficient model comes first.
+pub fn rank(smf: &smf::Smf, models: &[&'static Model]) -> Vec<Compatibility> {
+	let used = used_selections(smf);
+	let mut ret: Vec<Compatibility> = models.iter()
+		.map(|m| Compatibility::new(m, &used))
+		.collect();
+	ret.sort_by_key(|c| (c.missing.len(), c.fallback.len()));
+	ret
+}
+
+pub struct Table<'a>(pub &'a [Compatibility]);
+
+impl<'a> fmt::Display for Table<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let width = self.0.iter().map(|c| c.model.name.len()).max().unwrap_or(0).max(5);
+		writeln!(f, "{:width$}  Exact  Fallback  Missing", "Model", width = width)?;
+		for c in self.0 {
+			writeln!(f,
+				"{:width$}  {:>5}  {:>8}  {:>7}",
+				c.model.name, c.exact.len(), c.fallback.len(), c.missing.len(), width = width
+			)?;
+		}
+		Ok(())
+	}
+}
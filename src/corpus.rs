@@ -0,0 +1,281 @@
+/*
+ * Aggregate statistics over a directory tree of MIDI files.
+ */
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use super::compat::{used_selections, Compatibility};
+use super::detect::{self, Target, Verdict};
+use super::json::{self, Value};
+use super::midi::Selection;
+use super::models::{Model, Resolution};
+use super::smf::Smf;
+use super::voices::*;
+
+// Number of most used voices listed per model.
+const MOST_USED: usize = 20;
+
+const EXTENSIONS: &[&str] = &["mid", "midi", "kar", "rmi"];
+
+pub struct Scan {
+	// Sorted by path.
+	pub files: Vec<PathBuf>,
+	// Directories that couldn't be read, with their errors.
+	pub skipped: Vec<(PathBuf, io::Error)>,
+}
+
+// All MIDI files below `dir`. Symbolic links to directories aren't followed,
+// so that link loops can't recurse forever.
+pub fn scan<P: AsRef<Path>>(dir: P) -> io::Result<Scan> {
+	fn walk(dir: &Path, ret: &mut Vec<PathBuf>, skipped: &mut Vec<(PathBuf, io::Error)>)
+		-> io::Result<()>
+	{
+		for entry in fs::read_dir(dir)? {
+			let entry = entry?;
+			let path = entry.path();
+			if entry.file_type()?.is_dir() {
+				if let Err(e) = walk(&path, ret, skipped) {
+					skipped.push((path, e));
+				}
+			} else if path.extension()
+				.and_then(|ext| ext.to_str())
+				.is_some_and(|ext| EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+			{
+				ret.push(path);
+			}
+		}
+		Ok(())
+	}
+	let mut ret = Scan { files: Vec::new(), skipped: Vec::new() };
+	walk(dir.as_ref(), &mut ret.files, &mut ret.skipped)?;
+	ret.files.sort();
+	ret.skipped.sort_by(|a, b| a.0.cmp(&b.0));
+	Ok(ret)
+}
+
+pub struct FileStats {
+	pub verdict: Verdict,
+	pub used: BTreeSet<Selection>,
+	// In the order of the analysed models.
+	pub compat: Vec<Compatibility>,
+	// Addresses beyond GM, the GS drum kits and XG Level 1.
+	pub beyond_level1: BTreeSet<Selection>,
+}
+
+pub struct FileReport {
+	pub path: PathBuf,
+	pub result: Result<FileStats, String>,
+}
+
+fn is_level1(sel: Selection) -> bool {
+	[GM, GSDrums, XGLevel1].iter().any(|set| set.find(sel.msb, sel.lsb, sel.prg).is_some())
+}
+
+fn analyse_file(path: &Path, models: &[&'static Model]) -> FileReport {
+	// A bug triggered by one file shouldn't end the whole run.
+	let result = panic::catch_unwind(|| analyse_smf(path, models)).unwrap_or_else(|e| {
+		let message = e.downcast_ref::<&str>().map(|s| s.to_string())
+			.or_else(|| e.downcast_ref::<String>().cloned())
+			.unwrap_or_default();
+		Err(format!("internal error: {}", message))
+	});
+	FileReport { path: path.to_path_buf(), result }
+}
+
+fn analyse_smf(path: &Path, models: &[&'static Model]) -> Result<FileStats, String> {
+	Smf::load(path).map_err(|e| e.to_string()).map(|smf| {
+		let used = used_selections(&smf);
+		FileStats {
+			verdict: detect::detect(&smf),
+			compat: models.iter().map(|m| Compatibility::new(m, &used)).collect(),
+			// GS variation tones (MSB only) are not XG addresses at all.
+			beyond_level1: used.iter().cloned()
+				.filter(|&sel| !is_level1(sel))
+				.filter(|&sel| sel.lsb != 0 || VoiceKind::of_msb(sel.msb) != VoiceKind::Instrument)
+				.collect(),
+			used,
+		}
+	})
+}
+
+pub struct Report {
+	pub models: Vec<&'static Model>,
+	pub files: Vec<FileReport>,
+}
+
+// Analyses `paths` on all available cores. Files that fail to load are
+// reported with their error instead.
+pub fn analyse(paths: &[PathBuf], models: &[&'static Model]) -> Report {
+	let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(paths.len().max(1));
+	let next = AtomicUsize::new(0);
+	let mut files: Vec<(usize, FileReport)> = thread::scope(|s| {
+		let workers: Vec<_> = (0..threads).map(|_| s.spawn(|| {
+			let mut ret = Vec::new();
+			loop {
+				let i = next.fetch_add(1, Ordering::Relaxed);
+				match paths.get(i) {
+					Some(path) => ret.push((i, analyse_file(path, models))),
+					None => return ret,
+				}
+			}
+		})).collect();
+		// Workers catch panics per file, so joining them can't fail.
+		workers.into_iter().flat_map(|w| w.join().unwrap_or_default()).collect()
+	});
+	files.sort_by_key(|&(i, _)| i);
+	Report {
+		models: models.to_vec(),
+		files: files.into_iter().map(|(_, f)| f).collect(),
+	}
+}
+
+impl Report {
+	fn stats(&self) -> impl Iterator<Item = &FileStats> {
+		self.files.iter().filter_map(|f| f.result.as_ref().ok())
+	}
+
+	pub fn errors(&self) -> usize {
+		self.files.iter().filter(|f| f.result.is_err()).count()
+	}
+
+	pub fn standards(&self) -> BTreeMap<Target, usize> {
+		let mut ret = BTreeMap::new();
+		for stats in self.stats() {
+			*ret.entry(stats.verdict.target).or_insert(0) += 1;
+		}
+		ret
+	}
+
+	pub fn beyond_level1(&self) -> usize {
+		self.stats().filter(|s| !s.beyond_level1.is_empty()).count()
+	}
+
+	// Voices of `model` by the number of files that play them, most used
+	// first.
+	pub fn most_used(&self, model: &Model) -> Vec<(&'static Voice, usize)> {
+		let mut counts: BTreeMap<(u8, u8, u8), (&'static Voice, usize)> = BTreeMap::new();
+		for stats in self.stats() {
+			let voices: BTreeSet<_> = stats.used.iter()
+				.filter_map(|sel| match model.resolve(sel.msb, sel.lsb, sel.prg) {
+					Resolution::Exact(v) | Resolution::Fallback(v) => Some((v.msb, v.lsb, v.prg)),
+					Resolution::Missing => None,
+				})
+				.collect();
+			for key in voices {
+				let (msb, lsb, prg) = key;
+				let voice = model.find(msb, lsb, prg).unwrap();
+				counts.entry(key).or_insert((voice, 0)).1 += 1;
+			}
+		}
+		let mut ret: Vec<_> = counts.into_values().collect();
+		ret.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+		ret
+	}
+
+	// XG Level 1 voices that no file selects.
+	pub fn unused_xg_voices(&self) -> Vec<&'static Voice> {
+		let used: BTreeSet<Selection> = self.stats().flat_map(|s| s.used.iter().cloned()).collect();
+		XGLevel1.iter()
+			.filter(|v| !used.contains(&Selection { msb: v.msb, lsb: v.lsb, prg: v.prg }))
+			.collect()
+	}
+
+	pub fn to_json(&self) -> Value {
+		let string = |s: &str| Value::String(s.to_string());
+		let standards = self.standards().into_iter()
+			.map(|(t, n)| (t.to_string(), json::number(n as u32)))
+			.collect();
+		let models = self.models.iter().map(|m| json::object(vec![
+			("name", string(m.name)),
+			("most_used", Value::Array(self.most_used(m).into_iter().take(MOST_USED)
+				.map(|(v, n)| match json::voice_value(v) {
+					Value::Object(mut pairs) => {
+						pairs.push(("files".to_string(), json::number(n as u32)));
+						Value::Object(pairs)
+					}
+					_ => unreachable!(),
+				})
+				.collect())),
+		])).collect();
+		let files = self.files.iter().map(|f| {
+			let path = ("path", string(&f.path.to_string_lossy()));
+			match f.result {
+				Err(ref e) => json::object(vec![path, ("error", string(e))]),
+				Ok(ref stats) => json::object(vec![
+					path,
+					("standard", string(&stats.verdict.target.to_string())),
+					("confidence", stats.verdict.confidence.map_or(Value::Null, json::number)),
+					("compatibility", Value::Array(stats.compat.iter().map(|c| json::object(vec![
+						("model", string(c.model.name)),
+						("exact", json::number(c.exact.len() as u32)),
+						("fallback", json::number(c.fallback.len() as u32)),
+						("missing", json::number(c.missing.len() as u32)),
+					])).collect())),
+					("beyond_xg_level1", Value::Array(stats.beyond_level1.iter()
+						.map(|sel| string(&sel.to_string()))
+						.collect())),
+				]),
+			}
+		}).collect();
+		json::object(vec![
+			("files", json::number(self.files.len() as u32)),
+			("errors", json::number(self.errors() as u32)),
+			("standards", Value::Object(standards)),
+			("beyond_xg_level1", json::number(self.beyond_level1() as u32)),
+			("models", Value::Array(models)),
+			("unused_xg_level1", Value::Array(self.unused_xg_voices().into_iter()
+				.map(json::voice_value)
+				.collect())),
+			("file_reports", Value::Array(files)),
+		])
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Files: {} ({} unreadable)", self.files.len(), self.errors())?;
+		let standards: Vec<String> = self.standards().iter()
+			.map(|(t, n)| format!("{} {}", t, n))
+			.collect();
+		writeln!(f, "Standards: {}", standards.join(", "))?;
+		writeln!(f, "Files needing more than XG Level 1: {}", self.beyond_level1())?;
+		for model in &self.models {
+			writeln!(f)?;
+			writeln!(f, "Most used voices on {}:", model.name)?;
+			for (voice, n) in self.most_used(model).into_iter().take(MOST_USED) {
+				writeln!(f, "\t{:>5}  {}", n, voice)?;
+			}
+		}
+		let unused = self.unused_xg_voices();
+		writeln!(f)?;
+		writeln!(f, "Unused XG Level 1 voices ({} of {}):", unused.len(), XGLevel1.0.len())?;
+		for voice in unused {
+			writeln!(f, "\t{}", voice)?;
+		}
+		writeln!(f)?;
+		writeln!(f, "Files (exact/fallback/missing voices per model):")?;
+		for file in &self.files {
+			match file.result {
+				Err(ref e) => writeln!(f, "\t{}: error: {}", file.path.display(), e)?,
+				Ok(ref stats) => {
+					let compat: Vec<String> = stats.compat.iter()
+						.map(|c| format!(
+							"{} {}/{}/{}", c.model.name, c.exact.len(), c.fallback.len(), c.missing.len()
+						))
+						.collect();
+					writeln!(f,
+						"\t{}: {}, {}", file.path.display(), stats.verdict.target, compat.join(", ")
+					)?;
+				}
+			}
+		}
+		Ok(())
+	}
+}
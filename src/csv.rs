@@ -0,0 +1,169 @@
+/*
+ * CSV/TSV export of the voice database, with one row per unique voice
+ * address across all exported models.
+ */
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+
+use super::models::Model;
+use super::voices::*;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+	Csv,
+	Tsv,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+	Msb,
+	Lsb,
+	Program,
+	Name,
+	Kind,
+	// One boolean column per model, indicating whether the voice is
+	// available on that model.
+	Models,
+	// `Model=Name` pairs for all models that call the voice differently
+	// than the model providing the `Name` column.
+	OtherNames,
+}
+
+pub const COLUMNS: &[(&str, Column)] = &[
+	("msb", Column::Msb),
+	("lsb", Column::Lsb),
+	("program", Column::Program),
+	("name", Column::Name),
+	("kind", Column::Kind),
+	("models", Column::Models),
+	("other_names", Column::OtherNames),
+];
+
+impl FromStr for Column {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Column, String> {
+		COLUMNS.iter()
+			.find(|c| c.0 == s)
+			.map(|c| c.1)
+			.ok_or_else(|| {
+				let names: Vec<&str> = COLUMNS.iter().map(|c| c.0).collect();
+				format!("unknown column \"{}\", valid ones are {}", s, names.join(", "))
+			})
+	}
+}
+
+pub struct Options {
+	pub format: Format,
+	pub columns: Vec<Column>,
+	// Number of the first program, typically 1 as in Yamaha's manuals, or 0
+	// as in the raw Program Change value. MSB and LSB are always written as
+	// their raw controller values.
+	pub program_base: u8,
+}
+
+impl Default for Options {
+	fn default() -> Options {
+		Options {
+			format: Format::Csv,
+			columns: COLUMNS.iter().map(|c| c.1).collect(),
+			program_base: 1,
+		}
+	}
+}
+
+struct Row<'a> {
+	// Per exported model, in the same order.
+	voices: Vec<Option<&'a Voice>>,
+}
+
+impl<'a> Row<'a> {
+	// The voice that provides the address, name and kind columns.
+	fn first(&self) -> &'a Voice {
+		self.voices.iter().flatten().next().unwrap()
+	}
+}
+
+struct Field<T: fmt::Display>(Format, T);
+
+impl<T: fmt::Display> fmt::Display for Field<T> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let s = self.1.to_string();
+		match self.0 {
+			Format::Csv if s.contains([',', '"', '\n', '\r']) => {
+				write!(f, "\"{}\"", s.replace('"', "\"\""))
+			}
+			Format::Csv => write!(f, "{}", s),
+			// TSV has no quoting, so we can only replace any separators.
+			Format::Tsv => write!(f, "{}", s.replace(['\t', '\n', '\r'], " ")),
+		}
+	}
+}
+
+pub fn export<W: Write>(w: &mut W, models: &[&Model], opts: &Options) -> io::Result<()> {
+	let mut rows: BTreeMap<(u8, u8, u8), Row> = BTreeMap::new();
+	for (i, model) in models.iter().enumerate() {
+		for voice in model.voices() {
+			// Kits and SFX are conventionally sorted after instruments, which
+			// matches the order of their MSBs.
+			let row = rows.entry((voice.msb, voice.prg, voice.lsb)).or_insert_with(|| {
+				Row { voices: vec![None; models.len()] }
+			});
+			row.voices[i].get_or_insert(voice);
+		}
+	}
+
+	let sep = match opts.format {
+		Format::Csv => ",",
+		Format::Tsv => "\t",
+	};
+	let field = |s: &dyn fmt::Display| Field(opts.format, s.to_string());
+
+	let mut header = Vec::new();
+	for col in &opts.columns {
+		match *col {
+			Column::Models => {
+				header.extend(models.iter().map(|m| field(&m.name).to_string()));
+			}
+			col => {
+				let name = COLUMNS.iter().find(|c| c.1 == col).unwrap().0;
+				header.push(field(&name).to_string());
+			}
+		}
+	}
+	writeln!(w, "{}", header.join(sep))?;
+
+	for row in rows.values() {
+		let first = row.first();
+		let mut fields = Vec::new();
+		for col in &opts.columns {
+			match *col {
+				Column::Msb => fields.push(first.msb.to_string()),
+				Column::Lsb => fields.push(first.lsb.to_string()),
+				Column::Program => {
+					fields.push((first.prg - 1 + opts.program_base).to_string())
+				}
+				Column::Name => fields.push(field(&first.name()).to_string()),
+				Column::Kind => fields.push(first.kind().id().to_string()),
+				Column::Models => {
+					fields.extend(row.voices.iter().map(|v| v.is_some().to_string()))
+				}
+				Column::OtherNames => {
+					let others: Vec<String> = models.iter()
+						.zip(&row.voices)
+						.filter_map(|(m, v)| v.map(|v| (m, v)))
+						.filter(|(_, v)| v.name() != first.name())
+						.map(|(m, v)| format!("{}={}", m.name, v.name()))
+						.collect();
+					fields.push(field(&others.join("; ")).to_string());
+				}
+			}
+		}
+		writeln!(w, "{}", fields.join(sep))?;
+	}
+	Ok(())
+}
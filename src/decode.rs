@@ -0,0 +1,296 @@
+/*
+ * Explains System Exclusive messages, with a focus on XG.
+ */
+
+use std::fmt;
+
+use super::midi::*;
+use super::effectparams;
+use super::identity;
+use super::models::{Model, Resolution};
+use super::sysex::{self, Address, Hex};
+use super::xgmap::{self, Block, Format, Param};
+
+pub enum Line {
+	Info(String),
+	Warning(String),
+}
+
+impl fmt::Display for Line {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Line::Info(ref text) => f.write_str(text),
+			Line::Warning(ref text) => write!(f, "warning: {}", text),
+		}
+	}
+}
+
+// Decodes a sequence of messages, keeping track of the voice selected on
+// each part and the type of each effect block, so that bank, program and
+// effect parameters can be explained.
+pub struct Decoder {
+	pub model: &'static Model,
+	parts: [Selection; xgmap::MULTI_PARTS as usize],
+	// Type MSB and LSB, indexed by `EffectBlock`.
+	effects: [(u8, u8); 4],
+}
+
+// Parts 10 and 26 play drums.
+fn default_parts() -> [Selection; xgmap::MULTI_PARTS as usize] {
+	let mut ret = [Selection { msb: 0, lsb: 0, prg: 1 }; xgmap::MULTI_PARTS as usize];
+	ret[DRUM_CHANNEL as usize].msb = DRUM_MSB;
+	ret[DRUM_CHANNEL as usize + 16].msb = DRUM_MSB;
+	ret
+}
+
+fn default_effects() -> [(u8, u8); 4] {
+	let mut ret = [(0, 0); 4];
+	for param in xgmap::EFFECT {
+		if let (Format::EffectType(block), Some(v)) = (param.format, param.default) {
+			ret[block as usize] = ((v >> 7) as u8, (v & 0x7F) as u8);
+		}
+	}
+	ret
+}
+
+// Combines 7-bit bytes, most significant first.
+fn value(bytes: &[u8]) -> u32 {
+	bytes.iter().fold(0, |acc, &b| (acc << 7) | (b & 0x7F) as u32)
+}
+
+// As in "Dream (000-041-001)".
+pub fn voice(model: &Model, sel: Selection) -> String {
+	match model.resolve(sel.msb, sel.lsb, sel.prg) {
+		Resolution::Exact(v) => format!("{} ({})", v.name(), sel),
+		Resolution::Fallback(v) => {
+			let to = Selection { msb: v.msb, lsb: v.lsb, prg: v.prg };
+			format!("{} ({}, falls back to {})", v.name(), sel, to)
+		}
+		Resolution::Missing => format!("nothing ({} is not on {})", sel, model.name),
+	}
+}
+
+impl Decoder {
+	pub fn new(model: &'static Model) -> Decoder {
+		Decoder { model, parts: default_parts(), effects: default_effects() }
+	}
+
+	fn reset(&mut self) {
+		self.parts = default_parts();
+		self.effects = default_effects();
+	}
+
+	fn explain(&mut self, block: Block, param: &Param, data: &[u8]) -> Vec<Line> {
+		let v = param.value(data);
+		let mut ret = Vec::new();
+		if !param.available_on(self.model) {
+			ret.push(Line::Warning(format!(
+				"{}: {} is not available on {}", block, param.name, self.model.name
+			)));
+		}
+		if param.format == Format::Trigger {
+			if block == Block::System {
+				self.reset();
+			}
+			ret.push(Line::Info(format!("{}: {}", block, param.name)));
+			return ret;
+		}
+		if v < param.min || v > param.max {
+			ret.push(Line::Warning(format!(
+				"{}: {} = {} is out of range ({})", block, param.name, v, param.range()
+			)));
+			return ret;
+		}
+		if let Format::EffectType(effect) = param.format {
+			self.effects[effect as usize] = (data[0], data[1]);
+		}
+		let text = match param.format {
+			Format::BankMsb | Format::BankLsb | Format::Program => {
+				let part = match block {
+					Block::MultiPart(part) => part as usize,
+					_ => unreachable!(),
+				};
+				let sel = &mut self.parts[part];
+				match param.format {
+					Format::BankMsb => sel.msb = v as u8,
+					Format::BankLsb => sel.lsb = v as u8,
+					_ => sel.prg = v as u8 + 1,
+				}
+				let sel = *sel;
+				format!("{} → {}", v, voice(self.model, sel))
+			}
+			Format::EffectParam(effect, number) => {
+				let (msb, lsb) = self.effects[effect as usize];
+				let name = effect.find(msb, lsb).map_or("unknown type", |t| t.name);
+				match effectparams::param(msb, number) {
+					Some(p) if v < p.min || v > p.max => {
+						ret.push(Line::Warning(format!(
+							"{}: {} ({}) = {} is out of range for {}",
+							block, param.name, p.name, v, name
+						)));
+						return ret;
+					}
+					Some(p) => format!("{} ({} of {})", p.display(v), p.name, name),
+					None => format!("{} (unused by {})", v, name),
+				}
+			}
+			Format::EffectType(effect) if effect.find(data[0], data[1]).is_none() => {
+				ret.push(Line::Warning(format!(
+					"{}: {} = {} (unknown type)", block, param.name, param.format.display(v)
+				)));
+				return ret;
+			}
+			Format::EffectType(effect)
+				if ret.is_empty() && self.model.find_effect(effect, data[0], data[1]).is_none() =>
+			{
+				ret.push(Line::Warning(format!(
+					"{}: {} = {} is not available on {}",
+					block, param.name, param.format.display(v), self.model.name
+				)));
+				return ret;
+			}
+			format => format.display(v),
+		};
+		ret.push(Line::Info(format!("{}: {} = {}", block, param.name, text)));
+		ret
+	}
+
+	// Explains `data` written to consecutive parameters from `address`.
+	fn params(&mut self, address: Address, data: &[u8]) -> Vec<Line> {
+		let block = match Block::of(address) {
+			Some(block) => block,
+			None => return vec![Line::Warning(format!("unknown XG address {}", address))],
+		};
+		let mut ret = Vec::new();
+		let mut offset = address.low as usize;
+		let mut rest = data;
+		// Start of a run of bytes at unknown addresses.
+		let mut unknown: Option<usize> = None;
+		while !rest.is_empty() {
+			let param = if offset < 0x80 { block.param(offset as u8) } else { None };
+			let param = match param {
+				Some(param) => param,
+				None => {
+					unknown.get_or_insert(offset);
+					offset += 1;
+					rest = &rest[1..];
+					continue;
+				}
+			};
+			if let Some(start) = unknown.take() {
+				ret.push(unknown_range(block, address, start, offset));
+			}
+			let size = param.size as usize;
+			if rest.len() < size {
+				ret.push(Line::Warning(format!(
+					"{}: {} needs {} bytes, got {}", block, param.name, size, rest.len()
+				)));
+				return ret;
+			}
+			ret.extend(self.explain(block, param, &rest[..size]));
+			offset += size;
+			rest = &rest[size..];
+		}
+		if let Some(start) = unknown {
+			ret.push(unknown_range(block, address, start, offset));
+		}
+		ret
+	}
+
+	pub fn decode(&mut self, sysex: &[u8]) -> Vec<Line> {
+		let body = match sysex.split_first() {
+			Some((&0xF0, body)) => body,
+			_ => return vec![Line::Warning(format!("not a SysEx message: {}", Hex(sysex)))],
+		};
+		let (body, mut ret) = match body.split_last() {
+			Some((&0xF7, body)) => (body, Vec::new()),
+			_ => (body, vec![Line::Warning("missing F7 at the end".to_string())]),
+		};
+		let info = |text: &str| Line::Info(text.to_string());
+		match *body {
+			[0x7E, _, 0x09, 0x01] => {
+				self.reset();
+				ret.push(info("GM System On"));
+			}
+			[0x7E, _, 0x09, 0x02] => ret.push(info("GM System Off")),
+			[0x7E, _, 0x06, 0x01] => ret.push(info("Identity Request")),
+			[0x7E, _, 0x06, 0x02, ..] => ret.push(match identity::detect(sysex) {
+				Ok(detection) => Line::Info(format!("Identity Reply: {}", detection)),
+				Err(e) => Line::Warning(format!("Identity Reply: {}", e)),
+			}),
+			[0x7F, _, 0x04, 0x01, lsb, msb] => {
+				ret.push(Line::Info(format!("Master Volume = {}", value(&[msb, lsb]))));
+			}
+			[0x41, _, 0x42, 0x12, ref rest @ ..] if rest.len() >= 5 => {
+				let (&sum, rest) = rest.split_last().unwrap();
+				let expected = sysex::checksum(rest);
+				if sum != expected {
+					ret.push(Line::Warning(format!(
+						"GS checksum is {:02X}, expected {:02X}", sum, expected
+					)));
+				}
+				if is_reset(sysex) {
+					self.reset();
+					ret.push(info("GS Reset"));
+				} else {
+					ret.push(Line::Info(format!(
+						"GS parameter {} = {} (not decoded)", Hex(&rest[..3]), Hex(&rest[3..])
+					)));
+				}
+			}
+			[0x43, dev, 0x4C, ref rest @ ..] => match (dev & 0xF0, rest) {
+				(0x10, &[high, mid, low, ref data @ ..]) if !data.is_empty() => {
+					ret.extend(self.params(Address { high, mid, low }, data));
+				}
+				(0x00, &[bh, bl, high, mid, low, ref data @ ..]) if !data.is_empty() => {
+					let (&sum, data) = data.split_last().unwrap();
+					let expected = sysex::checksum(&body[3..body.len() - 1]);
+					if sum != expected {
+						ret.push(Line::Warning(format!(
+							"bulk dump checksum is {:02X}, expected {:02X}", sum, expected
+						)));
+					}
+					let count = value(&[bh, bl]) as usize;
+					if count != data.len() {
+						ret.push(Line::Warning(format!(
+							"bulk dump claims {} bytes, but has {}", count, data.len()
+						)));
+					}
+					let address = Address { high, mid, low };
+					ret.push(Line::Info(format!("Bulk dump of {} bytes at {}:", data.len(), address)));
+					ret.extend(self.params(address, data));
+				}
+				(0x20, &[high, mid, low]) | (0x30, &[high, mid, low]) => {
+					let kind = if dev & 0xF0 == 0x20 { "Bulk dump" } else { "Parameter" };
+					let address = Address { high, mid, low };
+					let what = match Block::of(address) {
+						Some(block) => match block.param(low) {
+							Some(param) => format!("{}: {}", block, param.name),
+							None => format!("{} at {}", block, address),
+						},
+						None => address.to_string(),
+					};
+					ret.push(Line::Info(format!("{} request for {}", kind, what)));
+				}
+				_ => ret.push(Line::Warning(format!("malformed XG message: {}", Hex(sysex)))),
+			},
+			[0x43, _, model, ..] => {
+				ret.push(Line::Info(format!("Yamaha message for model {:02X} (not decoded)", model)));
+			}
+			[id, ..] => {
+				ret.push(Line::Info(format!("message for manufacturer {:02X} (not decoded)", id)));
+			}
+			[] => ret.push(Line::Warning("empty message".to_string())),
+		}
+		ret
+	}
+}
+
+fn unknown_range(block: Block, address: Address, start: usize, end: usize) -> Line {
+	let at = |offset: usize| Address { low: offset as u8, ..address };
+	if end - start == 1 {
+		Line::Warning(format!("{}: unknown address {}", block, at(start)))
+	} else {
+		Line::Warning(format!("{}: unknown addresses {} to {}", block, at(start), at(end - 1)))
+	}
+}
@@ -0,0 +1,186 @@
+/*
+ * Detects the standard (GM, GS or XG) that an SMF was written for.
+ *
+ * Every hint found in the file adds a weight to one standard. The standard
+ * with the highest total wins, and its share of all weights is the
+ * confidence, which is unknown if there are no hints at all. Weights are
+ * chosen so that a reset SysEx outweighs a handful of voice addresses, since
+ * addresses are often shared between standards.
+ */
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::compat::used_selections;
+use super::midi::*;
+use super::smf::{Event, Smf};
+use super::voices::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Target {
+	Gm,
+	Gs,
+	Xg,
+}
+
+impl fmt::Display for Target {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			Target::Gm => "GM",
+			Target::Gs => "GS",
+			Target::Xg => "XG",
+		})
+	}
+}
+
+pub struct Evidence {
+	pub target: Target,
+	pub weight: u32,
+	pub description: String,
+}
+
+impl fmt::Display for Evidence {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} +{}: {}", self.target, self.weight, self.description)
+	}
+}
+
+pub struct Verdict {
+	pub target: Target,
+	// 0.0-1.0, None without any evidence.
+	pub confidence: Option<f64>,
+	pub evidence: Vec<Evidence>,
+}
+
+impl fmt::Display for Verdict {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.confidence {
+			Some(confidence) => {
+				writeln!(f, "{} ({:.0}% confidence)", self.target, confidence * 100.0)?;
+			}
+			None => writeln!(f, "{} (unknown confidence)", self.target)?,
+		}
+		for it in &self.evidence {
+			writeln!(f, "\t{}", it)?;
+		}
+		Ok(())
+	}
+}
+
+// Weights added to the winner's score and to the total when computing the
+// confidence, so that a single weak hint doesn't result in a confident
+// verdict. Every hint for the winner raises the confidence.
+const PRIOR_HIT: u32 = 1;
+const PRIOR: u32 = 2;
+
+// Lists up to a few addresses, as in "000-001-001, 000-008-005, ...".
+fn addresses(sels: &BTreeSet<Selection>) -> String {
+	let mut ret = sels.iter().take(4).map(|s| s.to_string()).collect::<Vec<_>>().join(", ");
+	if sels.len() > 4 {
+		ret += ", ...";
+	}
+	ret
+}
+
+pub fn detect(smf: &Smf) -> Verdict {
+	let mut evidence = Vec::new();
+	let mut add = |target, weight, description: String| {
+		evidence.push(Evidence { target, weight, description })
+	};
+
+	let (mut gm_on, mut gs_reset, mut xg_on) = (0, 0, 0);
+	let (mut roland, mut yamaha) = (0, 0);
+	let (mut msb_sent, mut lsb_sent) = (false, false);
+	for track in &smf.tracks {
+		for e in &track.events {
+			match e.event {
+				Event::SysEx(ref data) => match (data.get(1), is_reset(data)) {
+					(Some(&0x7E), true) => gm_on += 1,
+					(Some(&0x41), true) => gs_reset += 1,
+					(Some(&0x43), true) => xg_on += 1,
+					(Some(&0x41), false) => roland += 1,
+					(Some(&0x43), false) => yamaha += 1,
+					_ => {}
+				},
+				Event::Channel { msg: ChannelMsg::Control { controller, value }, .. } => {
+					match controller {
+						CC_BANK_MSB if value != 0 => msb_sent = true,
+						CC_BANK_LSB => lsb_sent = true,
+						_ => {}
+					}
+				}
+				_ => {}
+			}
+		}
+	}
+	if gm_on > 0 {
+		add(Target::Gm, 3, format!("GM System On ({}x)", gm_on));
+	}
+	if gs_reset > 0 {
+		add(Target::Gs, 10, format!("GS Reset ({}x)", gs_reset));
+	}
+	if xg_on > 0 {
+		add(Target::Xg, 10, format!("XG System On ({}x)", xg_on));
+	}
+	if roland > 0 {
+		add(Target::Gs, 2, format!("other Roland SysEx ({}x)", roland));
+	}
+	if yamaha > 0 {
+		add(Target::Xg, 2, format!("other Yamaha SysEx ({}x)", yamaha));
+	}
+	if msb_sent && !lsb_sent {
+		add(Target::Gs, 1, "bank select MSBs without LSBs".to_string());
+	}
+
+	let (mut xg, mut xg_style, mut gs, mut kits) = Default::default();
+	for sel in used_selections(smf) {
+		let set: &mut BTreeSet<Selection> = match VoiceKind::of_msb(sel.msb) {
+			VoiceKind::Kit if sel.msb == 126 => &mut xg,
+			// Every standard has a Standard Kit.
+			VoiceKind::Kit if sel.lsb == 0 && sel.prg == 1 => continue,
+			VoiceKind::Kit if sel.lsb == 0 && GSDrums.find(127, 0, sel.prg).is_some() => {
+				&mut kits
+			}
+			_ if XGLevel1.find(sel.msb, sel.lsb, sel.prg).is_some() => &mut xg,
+			_ if sel.lsb != 0 => &mut xg_style,
+			VoiceKind::Instrument if sel.msb != 0 => &mut gs,
+			_ => continue,
+		};
+		set.insert(sel);
+	}
+	if !xg.is_empty() {
+		add(Target::Xg, 2 * xg.len() as u32, format!("XG voices {}", addresses(&xg)));
+	}
+	if !xg_style.is_empty() {
+		let description = format!("unknown voices with an LSB {}", addresses(&xg_style));
+		add(Target::Xg, xg_style.len() as u32, description);
+	}
+	if !gs.is_empty() {
+		let description = format!("GS variation tones {}", addresses(&gs));
+		add(Target::Gs, 2 * gs.len() as u32, description);
+	}
+	if !kits.is_empty() {
+		// The GS drum kits are shared by XG, but not part of GM.
+		let description = format!("GS drum kits {}", addresses(&kits));
+		add(Target::Gs, kits.len() as u32, description);
+	}
+
+	if evidence.is_empty() {
+		let description = "nothing specific to GS or XG".to_string();
+		evidence.push(Evidence { target: Target::Gm, weight: 0, description });
+	}
+	let score = |target| -> u32 {
+		evidence.iter().filter(|e| e.target == target).map(|e| e.weight).sum()
+	};
+	let total: u32 = evidence.iter().map(|e| e.weight).sum();
+	// GM wins ties, since it is the common subset.
+	let target = [Target::Gm, Target::Gs, Target::Xg].iter().cloned()
+		.rev()
+		.max_by_key(|&t| score(t))
+		.unwrap();
+	let confidence = match total {
+		0 => None,
+		_ => Some((score(target) + PRIOR_HIT) as f64 / (total + PRIOR) as f64),
+	};
+	Verdict { target, confidence, evidence }
+}
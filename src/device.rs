@@ -0,0 +1,364 @@
+/*
+ * A virtual XG module, standing in for the hardware when trying out System
+ * Exclusive features.
+ *
+ * It reads a raw MIDI byte stream and keeps the parameter memory of the
+ * System, Effect, Multi Part and Drum Setup blocks like the module does:
+ * resets, parameter changes, bulk dumps, and Bank Select and Program Change
+ * on the channels that parts receive. It answers Identity Requests, Dump
+ * Requests and Parameter Requests with the messages the module would send.
+ *
+ * Parameters whose default depends on the drum instrument start out as 0.
+ */
+
+use std::collections::BTreeMap;
+
+use super::dump::Part;
+use super::effects::EffectBlock;
+use super::identity;
+use super::midi::{self, ChannelMsg, Message, Parser, Selection};
+use super::midi::{CC_BANK_LSB, CC_BANK_MSB, DRUM_MSB};
+use super::models::Model;
+use super::sysex::{self, Address, MultiPart, XgMessage};
+use super::xgmap::{self, Block, Format};
+
+// MSB of the SFX kit bank, which is played in drum mode like DRUM_MSB.
+const SFX_KIT_MSB: u8 = 126;
+
+pub struct Device {
+	pub model: &'static Model,
+	// 0-15, as on the panel minus 1.
+	pub number: u8,
+	// Parameter memory, indexed by the high and middle address bytes.
+	memory: BTreeMap<(u8, u8), [u8; 0x80]>,
+	// Parts whose parameters were set since the last reset.
+	touched: [bool; xgmap::MULTI_PARTS as usize],
+	parser: Parser,
+	// Number of messages that the module ignores.
+	pub ignored: usize,
+}
+
+fn key(block: Block) -> (u8, u8) {
+	match block {
+		Block::System => (0x00, 0x00),
+		Block::Effect => (0x02, 0x01),
+		Block::MultiPart(part) => (0x08, part),
+		Block::DrumSetup(setup, note) => (0x30 | setup, note),
+	}
+}
+
+impl Device {
+	pub fn new(model: &'static Model, number: u8) -> Result<Device, String> {
+		if !model.is_xg() {
+			return Err(format!("{} is not an XG module", model.name));
+		}
+		let mut ret = Device {
+			model,
+			number: number & 0x0F,
+			memory: BTreeMap::new(),
+			touched: [false; xgmap::MULTI_PARTS as usize],
+			parser: Default::default(),
+			ignored: 0,
+		};
+		ret.reset();
+		Ok(ret)
+	}
+
+	fn blocks() -> impl Iterator<Item = Block> {
+		let parts = (0..xgmap::MULTI_PARTS).map(Block::MultiPart);
+		let drums = (0..xgmap::DRUM_SETUPS)
+			.flat_map(|setup| (0..128).map(move |note| Block::DrumSetup(setup, note)));
+		vec![Block::System, Block::Effect].into_iter().chain(parts).chain(drums)
+	}
+
+	fn reset(&mut self) {
+		self.memory.clear();
+		for block in Device::blocks() {
+			let mut data = [0; 0x80];
+			for param in block.params() {
+				let default = match (block, param.default) {
+					(_, Some(v)) => v,
+					(Block::MultiPart(part), None) => {
+						let power_on = Part::new(part);
+						match param.offset {
+							o if o == MultiPart::BankMsb as u8 => power_on.sel.msb as u32,
+							o if o == MultiPart::PartMode as u8 => power_on.mode as u32,
+							o if o == MultiPart::RcvChannel as u8 => power_on.channel as u32,
+							_ => 0,
+						}
+					}
+					_ => 0,
+				};
+				let offset = param.offset as usize;
+				data[offset..offset + param.size as usize].copy_from_slice(&param.bytes(default));
+			}
+			self.memory.insert(key(block), data);
+		}
+		self.touched = [false; xgmap::MULTI_PARTS as usize];
+	}
+
+	// Feeds raw MIDI bytes to the module, returning the messages it sent
+	// back.
+	pub fn send(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+		let mut ret = Vec::new();
+		for &byte in bytes {
+			let reply = match self.parser.feed(byte) {
+				Some(Message::Channel(channel, msg)) => {
+					self.channel(channel, msg);
+					None
+				}
+				Some(Message::SysEx(sysex)) => self.sysex(&sysex),
+				None => None,
+			};
+			ret.extend(reply);
+		}
+		ret
+	}
+
+	// Number of bytes that were not part of a complete message.
+	pub fn dropped(&self) -> usize {
+		self.parser.dropped
+	}
+
+	// Bank Select and Program Change on a channel, which set the parameters
+	// of all parts receiving it. Bank Select only takes effect with the next
+	// Program Change, which switches the part to drum mode for the kit banks
+	// and back.
+	fn channel(&mut self, channel: u8, msg: ChannelMsg) {
+		for part in 0..xgmap::MULTI_PARTS {
+			let data = self.memory.get_mut(&key(Block::MultiPart(part))).unwrap();
+			if data[MultiPart::RcvChannel as usize] != channel {
+				continue;
+			}
+			match msg {
+				ChannelMsg::Control { controller: CC_BANK_MSB, value } => {
+					data[MultiPart::BankMsb as usize] = value;
+				}
+				ChannelMsg::Control { controller: CC_BANK_LSB, value } => {
+					data[MultiPart::BankLsb as usize] = value;
+				}
+				ChannelMsg::Program(prg) => {
+					data[MultiPart::Program as usize] = prg;
+					let msb = data[MultiPart::BankMsb as usize];
+					let mode = &mut data[MultiPart::PartMode as usize];
+					let kit = msb == DRUM_MSB || msb == SFX_KIT_MSB;
+					if kit && *mode == sysex::PART_MODE_NORMAL {
+						*mode = sysex::PART_MODE_DRUM;
+					} else if !kit && *mode != sysex::PART_MODE_NORMAL {
+						*mode = sysex::PART_MODE_NORMAL;
+					}
+				}
+				_ => continue,
+			}
+			self.touched[part as usize] = true;
+		}
+	}
+
+	fn sysex(&mut self, sysex: &[u8]) -> Option<Vec<u8>> {
+		let message = match sysex::parse_xg(sysex) {
+			Some(Ok((device, message))) if device == self.number => message,
+			Some(_) => {
+				self.ignored += 1;
+				return None;
+			}
+			None => {
+				match *sysex {
+					[0xF0, 0x7E, device, 0x06, 0x01, 0xF7]
+						if device == 0x7F || device == self.number =>
+					{
+						return identity::reply(self.number, self.model);
+					}
+					_ if midi::is_reset(sysex) => self.reset(),
+					_ => self.ignored += 1,
+				}
+				return None;
+			}
+		};
+		match message {
+			XgMessage::ParameterChange(address, data) => {
+				match xgmap::find(address) {
+					Some((_, param))
+						if param.available_on(self.model) && param.size as usize == data.len() =>
+					{
+						self.write(address, data);
+					}
+					_ => self.ignored += 1,
+				}
+				None
+			}
+			XgMessage::BulkDump(address, data) => {
+				if Block::of(address).is_some() && address.low as usize + data.len() <= 0x80 {
+					self.write(address, data);
+				} else {
+					self.ignored += 1;
+				}
+				None
+			}
+			XgMessage::DumpRequest(address) => {
+				let reply = self.read(address, None);
+				if reply.is_none() {
+					self.ignored += 1;
+				}
+				reply.map(|data| sysex::xg_bulk_dump(self.number, address, &data))
+			}
+			XgMessage::ParameterRequest(address) => {
+				let size = match xgmap::find(address) {
+					Some((_, param)) if param.available_on(self.model) => param.size as usize,
+					_ => {
+						self.ignored += 1;
+						return None;
+					}
+				};
+				self.read(address, Some(size))
+					.map(|data| sysex::xg_parameter_change(self.number, address, &data))
+			}
+		}
+	}
+
+	fn write(&mut self, address: Address, data: &[u8]) {
+		let block = match Block::of(address) {
+			Some(block) => block,
+			None => return,
+		};
+		let written = address.low as usize..address.low as usize + data.len();
+		if block == Block::System {
+			let reset = block.params().iter()
+				.any(|p| p.format == Format::Trigger && written.contains(&(p.offset as usize)));
+			if reset {
+				return self.reset();
+			}
+		}
+		self.memory.get_mut(&key(block)).unwrap()[written].copy_from_slice(data);
+		if let Block::MultiPart(part) = block {
+			self.touched[part as usize] = true;
+		}
+	}
+
+	// `size` bytes from `address`, or up to the last parameter of its block
+	// that the model has.
+	fn read(&self, address: Address, size: Option<usize>) -> Option<Vec<u8>> {
+		let block = Block::of(address)?;
+		let end = block.params().iter()
+			.filter(|p| p.available_on(self.model))
+			.map(|p| p.offset as usize + p.size as usize)
+			.max()?;
+		let start = address.low as usize;
+		let end = size.map_or(end, |size| start + size);
+		if start >= end {
+			return None;
+		}
+		Some(self.memory[&key(block)][start..end].to_vec())
+	}
+
+	// The state of a 0-based part.
+	pub fn part(&self, part: u8) -> Part {
+		let data = &self.memory[&key(Block::MultiPart(part))];
+		Part {
+			number: part,
+			channel: data[MultiPart::RcvChannel as usize],
+			mode: data[MultiPart::PartMode as usize],
+			sel: Selection {
+				msb: data[MultiPart::BankMsb as usize],
+				lsb: data[MultiPart::BankLsb as usize],
+				prg: data[MultiPart::Program as usize] + 1,
+			},
+			touched: self.touched[part as usize],
+		}
+	}
+
+	pub fn parts(&self) -> Vec<Part> {
+		(0..xgmap::MULTI_PARTS).map(|part| self.part(part)).collect()
+	}
+
+	// The type MSB and LSB of an effect block, if the XG map has its type
+	// parameter.
+	pub fn effect(&self, block: EffectBlock) -> Option<(u8, u8)> {
+		let param = xgmap::EFFECT.iter().find(|p| p.format == Format::EffectType(block))?;
+		let data = &self.memory[&key(Block::Effect)];
+		Some((data[param.offset as usize], data[param.offset as usize + 1]))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::dump::Dump;
+	use super::super::models;
+
+	fn mu80() -> Device {
+		Device::new(models::find("MU80").unwrap(), 0).unwrap()
+	}
+
+	fn sel(msb: u8, lsb: u8, prg: u8) -> Selection {
+		Selection { msb, lsb, prg }
+	}
+
+	#[test]
+	fn system_on_resets() {
+		let mut device = mu80();
+		device.send(&[0xB0, 0x00, 0x00, 0x20, 0x08, 0xC0, 0x04]);
+		let variation_type = Address { high: 0x02, mid: 0x01, low: 0x40 };
+		device.send(&sysex::xg_parameter_change(0, variation_type, &[0x49, 0x00]));
+		assert!(device.part(0).touched);
+		assert_eq!(device.effect(EffectBlock::Variation), Some((0x49, 0x00)));
+		assert!(device.send(&sysex::xg_system_on(0)).is_empty());
+		let part = device.part(0);
+		assert!(!part.touched);
+		assert_eq!(part.sel, sel(0, 0, 1));
+		assert_eq!(device.part(9).sel, sel(DRUM_MSB, 0, 1));
+		assert_eq!(device.effect(EffectBlock::Variation), Some((0x05, 0x00)));
+	}
+
+	#[test]
+	fn bank_select_and_program_change() {
+		let mut device = mu80();
+		// Running status, with a realtime byte in between.
+		device.send(&[0xB1, 0x00, 0x00, 0xF8, 0x20, 0x08, 0xC1, 0x04]);
+		assert_eq!(device.part(1).sel, sel(0, 8, 5));
+		assert_eq!(device.part(1).mode, sysex::PART_MODE_NORMAL);
+		assert_eq!(device.part(0).sel, sel(0, 0, 1));
+		// Kit banks switch the part to drum mode.
+		device.send(&[0xB2, 0x00, DRUM_MSB, 0xC2, 0x00]);
+		assert_eq!(device.part(2).mode, sysex::PART_MODE_DRUM);
+	}
+
+	#[test]
+	fn parameter_change_then_program_change() {
+		let mut device = mu80();
+		let bank_lsb = Address::multi_part(0, MultiPart::BankLsb);
+		device.send(&sysex::xg_parameter_change(0, bank_lsb, &[0x29]));
+		assert_eq!(device.part(0).sel, sel(0, 41, 1));
+		device.send(&[0xC0, 0x00]);
+		assert_eq!(device.part(0).sel, sel(0, 41, 1));
+	}
+
+	#[test]
+	fn identity_request() {
+		let mut device = mu80();
+		let replies = device.send(&sysex::identity_request(0x7F));
+		assert_eq!(replies.len(), 1);
+		let detection = identity::detect(&replies[0]).unwrap();
+		assert_eq!(detection.model.map(|m| m.name), Some("MU80"));
+		// Requests for other devices go unanswered.
+		assert!(device.send(&sysex::identity_request(0x05)).is_empty());
+	}
+
+	#[test]
+	fn bulk_dump_request_round_trips() {
+		let mut device = mu80();
+		device.send(&[0xB3, 0x00, 0x00, 0x20, 0x10, 0xC3, 0x30]);
+		let replies = device.send(&sysex::xg_dump_request(0, Address { high: 8, mid: 3, low: 0 }));
+		assert_eq!(replies.len(), 1);
+		let dump = Dump::parse(&replies[0]);
+		assert!(dump.warnings.is_empty(), "{:?}", dump.warnings);
+		let parts = dump.dumped_parts().unwrap();
+		assert_eq!(parts.len(), 1);
+		assert_eq!(parts[0].number, 3);
+		assert_eq!(parts[0].sel, sel(0, 16, 49));
+		assert_eq!(parts[0].channel, 3);
+		// Another module takes the dump as it is.
+		let mut other = mu80();
+		assert!(other.send(&replies[0]).is_empty());
+		assert_eq!(other.part(3).sel, sel(0, 16, 49));
+	}
+}
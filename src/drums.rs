@@ -0,0 +1,430 @@
+/*
+ * Note maps of drum kits.
+ *
+ * Kits are stored as the differences to the standard kit of their family,
+ * since most kits only replace a handful of instruments.
+ */
+
+use std::collections::BTreeMap;
+
+use super::voices::Voice;
+
+pub struct NoteMap {
+	pub base: Option<&'static NoteMap>,
+	pub notes: &'static [(u8, &'static str)],
+}
+
+impl NoteMap {
+	// All notes of the kit, including those inherited from the base.
+	pub fn notes(&self) -> BTreeMap<u8, &'static str> {
+		let mut ret = self.base.map_or_else(BTreeMap::new, |base| base.notes());
+		ret.extend(self.notes.iter().cloned());
+		ret
+	}
+}
+
+// SC-55 Standard Set.
+static GS_STANDARD: NoteMap = NoteMap { base: None, notes: &[
+	(27, "High Q"),
+	(28, "Slap"),
+	(29, "Scratch Push"),
+	(30, "Scratch Pull"),
+	(31, "Sticks"),
+	(32, "Square Click"),
+	(33, "Metronome Click"),
+	(34, "Metronome Bell"),
+	(35, "Kick Drum 2"),
+	(36, "Kick Drum 1"),
+	(37, "Side Stick"),
+	(38, "Snare Drum 1"),
+	(39, "Hand Clap"),
+	(40, "Snare Drum 2"),
+	(41, "Low Tom 2"),
+	(42, "Closed Hi-hat"),
+	(43, "Low Tom 1"),
+	(44, "Pedal Hi-hat"),
+	(45, "Mid Tom 2"),
+	(46, "Open Hi-hat"),
+	(47, "Mid Tom 1"),
+	(48, "High Tom 2"),
+	(49, "Crash Cymbal 1"),
+	(50, "High Tom 1"),
+	(51, "Ride Cymbal 1"),
+	(52, "Chinese Cymbal"),
+	(53, "Ride Bell"),
+	(54, "Tambourine"),
+	(55, "Splash Cymbal"),
+	(56, "Cowbell"),
+	(57, "Crash Cymbal 2"),
+	(58, "Vibra-slap"),
+	(59, "Ride Cymbal 2"),
+	(60, "High Bongo"),
+	(61, "Low Bongo"),
+	(62, "Mute High Conga"),
+	(63, "Open High Conga"),
+	(64, "Low Conga"),
+	(65, "High Timbale"),
+	(66, "Low Timbale"),
+	(67, "High Agogo"),
+	(68, "Low Agogo"),
+	(69, "Cabasa"),
+	(70, "Maracas"),
+	(71, "Short Hi Whistle"),
+	(72, "Long Low Whistle"),
+	(73, "Short Guiro"),
+	(74, "Long Guiro"),
+	(75, "Claves"),
+	(76, "High Wood Block"),
+	(77, "Low Wood Block"),
+	(78, "Mute Cuica"),
+	(79, "Open Cuica"),
+	(80, "Mute Triangle"),
+	(81, "Open Triangle"),
+	(82, "Shaker"),
+	(83, "Jingle Bell"),
+	(84, "Belltree"),
+	(85, "Castanets"),
+	(86, "Mute Surdo"),
+	(87, "Open Surdo"),
+]};
+
+const GS_ROOM_TOMS: &[(u8, &str)] = &[
+	(41, "Room Low Tom 2"),
+	(43, "Room Low Tom 1"),
+	(45, "Room Mid Tom 2"),
+	(47, "Room Mid Tom 1"),
+	(48, "Room Hi Tom 2"),
+	(50, "Room Hi Tom 1"),
+];
+
+static GS_ROOM: NoteMap = NoteMap { base: Some(&GS_STANDARD), notes: GS_ROOM_TOMS };
+
+// Uses the room toms.
+static GS_POWER: NoteMap = NoteMap { base: Some(&GS_ROOM), notes: &[
+	(36, "MONDO Kick"),
+	(38, "Gated SD"),
+]};
+
+static GS_ELECTRONIC: NoteMap = NoteMap { base: Some(&GS_STANDARD), notes: &[
+	(36, "Elec BD"),
+	(38, "Elec SD"),
+	(40, "Gated SD"),
+	(41, "Elec Low Tom 2"),
+	(43, "Elec Low Tom 1"),
+	(45, "Elec Mid Tom 2"),
+	(47, "Elec Mid Tom 1"),
+	(48, "Elec Hi Tom 2"),
+	(50, "Elec Hi Tom 1"),
+	(52, "Reverse Cymbal"),
+]};
+
+static GS_TR808: NoteMap = NoteMap { base: Some(&GS_STANDARD), notes: &[
+	(36, "808 Bass Drum"),
+	(37, "808 Rim Shot"),
+	(38, "808 Snare Drum"),
+	(41, "808 Low Tom 2"),
+	(42, "808 CHH"),
+	(43, "808 Low Tom 1"),
+	(44, "808 CHH"),
+	(45, "808 Mid Tom 2"),
+	(46, "808 OHH"),
+	(47, "808 Mid Tom 1"),
+	(48, "808 Hi Tom 2"),
+	(49, "808 Cymbal"),
+	(50, "808 Hi Tom 1"),
+	(56, "808 Cowbell"),
+	(62, "808 High Conga"),
+	(63, "808 Mid Conga"),
+	(64, "808 Low Conga"),
+	(70, "808 Maracas"),
+	(75, "808 Claves"),
+]};
+
+static GS_JAZZ: NoteMap = NoteMap { base: Some(&GS_STANDARD), notes: &[
+	(35, "Jazz BD 2"),
+	(36, "Jazz BD 1"),
+]};
+
+static GS_BRUSH: NoteMap = NoteMap { base: Some(&GS_JAZZ), notes: &[
+	(38, "Brush Tap"),
+	(39, "Brush Slap"),
+	(40, "Brush Swirl"),
+]};
+
+static GS_ORCHESTRA: NoteMap = NoteMap { base: Some(&GS_STANDARD), notes: &[
+	(27, "Closed Hi-hat"),
+	(28, "Pedal Hi-hat"),
+	(29, "Open Hi-hat"),
+	(30, "Ride Cymbal"),
+	(35, "Concert BD 2"),
+	(36, "Concert BD 1"),
+	(38, "Concert SD"),
+	(39, "Castanets"),
+	(40, "Concert SD"),
+	(41, "Timpani F"),
+	(42, "Timpani F#"),
+	(43, "Timpani G"),
+	(44, "Timpani G#"),
+	(45, "Timpani A"),
+	(46, "Timpani A#"),
+	(47, "Timpani B"),
+	(48, "Timpani c"),
+	(49, "Timpani c#"),
+	(50, "Timpani d"),
+	(51, "Timpani d#"),
+	(52, "Timpani e"),
+	(53, "Timpani f"),
+	(57, "Concert Cymbal 2"),
+	(59, "Concert Cymbal 1"),
+	(88, "Applause"),
+]};
+
+// MU80 Standard Kit 1.
+static XG_STANDARD: NoteMap = NoteMap { base: None, notes: &[
+	(13, "Surdo Mute"),
+	(14, "Surdo Open"),
+	(15, "Hi Q"),
+	(16, "Whip Slap"),
+	(17, "Scratch Push"),
+	(18, "Scratch Pull"),
+	(19, "Finger Snap"),
+	(20, "Click Noise"),
+	(21, "Metronome Click"),
+	(22, "Metronome Bell"),
+	(23, "Seq Click L"),
+	(24, "Seq Click H"),
+	(25, "Brush Tap"),
+	(26, "Brush Swirl L"),
+	(27, "Brush Slap"),
+	(28, "Brush Swirl H"),
+	(29, "Snare Roll"),
+	(30, "Castanet"),
+	(31, "Snare L"),
+	(32, "Sticks"),
+	(33, "Bass Drum L"),
+	(34, "Open Rim Shot"),
+	(35, "Bass Drum M"),
+	(36, "Bass Drum H"),
+	(37, "Side Stick"),
+	(38, "Snare M"),
+	(39, "Hand Clap"),
+	(40, "Snare H"),
+	(41, "Floor Tom L"),
+	(42, "Hi-Hat Closed"),
+	(43, "Floor Tom H"),
+	(44, "Hi-Hat Pedal"),
+	(45, "Low Tom"),
+	(46, "Hi-Hat Open"),
+	(47, "Mid Tom L"),
+	(48, "Mid Tom H"),
+	(49, "Crash Cymbal 1"),
+	(50, "High Tom"),
+	(51, "Ride Cymbal 1"),
+	(52, "Chinese Cymbal"),
+	(53, "Ride Cymbal Cup"),
+	(54, "Tambourine"),
+	(55, "Splash Cymbal"),
+	(56, "Cowbell"),
+	(57, "Crash Cymbal 2"),
+	(58, "Vibraslap"),
+	(59, "Ride Cymbal 2"),
+	(60, "Bongo H"),
+	(61, "Bongo L"),
+	(62, "Conga H Mute"),
+	(63, "Conga H Open"),
+	(64, "Conga L"),
+	(65, "Timbale H"),
+	(66, "Timbale L"),
+	(67, "Agogo H"),
+	(68, "Agogo L"),
+	(69, "Cabasa"),
+	(70, "Maracas"),
+	(71, "Samba Whistle H"),
+	(72, "Samba Whistle L"),
+	(73, "Guiro Short"),
+	(74, "Guiro Long"),
+	(75, "Claves"),
+	(76, "Wood Block H"),
+	(77, "Wood Block L"),
+	(78, "Cuica Mute"),
+	(79, "Cuica Open"),
+	(80, "Triangle Mute"),
+	(81, "Triangle Open"),
+	(82, "Shaker"),
+	(83, "Jingle Bell"),
+	(84, "Bell Tree"),
+]};
+
+static XG_ROOM: NoteMap = NoteMap { base: Some(&XG_STANDARD), notes: &[
+	(38, "Snare Room L"),
+	(40, "Snare Room H"),
+	(41, "Room Tom 1"),
+	(43, "Room Tom 2"),
+	(45, "Room Tom 3"),
+	(47, "Room Tom 4"),
+	(48, "Room Tom 5"),
+	(50, "Room Tom 6"),
+]};
+
+static XG_ROCK: NoteMap = NoteMap { base: Some(&XG_STANDARD), notes: &[
+	(36, "Bass Drum Rock"),
+	(38, "Snare Rock"),
+	(40, "Snare Rock Rim"),
+	(41, "Rock Tom 1"),
+	(43, "Rock Tom 2"),
+	(45, "Rock Tom 3"),
+	(47, "Rock Tom 4"),
+	(48, "Rock Tom 5"),
+	(50, "Rock Tom 6"),
+]};
+
+static XG_ELECTRO: NoteMap = NoteMap { base: Some(&XG_STANDARD), notes: &[
+	(28, "Reverse Cymbal"),
+	(35, "Bass Drum H"),
+	(36, "Bass Drum Gate"),
+	(38, "Snare Rock"),
+	(40, "Snare Gate"),
+	(41, "E Tom 1"),
+	(43, "E Tom 2"),
+	(45, "E Tom 3"),
+	(47, "E Tom 4"),
+	(48, "E Tom 5"),
+	(50, "E Tom 6"),
+	(78, "Scratch H"),
+	(79, "Scratch L"),
+]};
+
+// Keeps the reverse cymbal and the scratches of the Electro Kit.
+static XG_ANALOG: NoteMap = NoteMap { base: Some(&XG_ELECTRO), notes: &[
+	(35, "Bass Drum Analog L"),
+	(36, "Bass Drum Analog H"),
+	(37, "Analog Side Stick"),
+	(38, "Analog Snare 1"),
+	(40, "Analog Snare 2"),
+	(41, "Analog Tom 1"),
+	(42, "Analog HH Closed 1"),
+	(43, "Analog Tom 2"),
+	(44, "Analog HH Closed 2"),
+	(45, "Analog Tom 3"),
+	(46, "Analog HH Open"),
+	(47, "Analog Tom 4"),
+	(48, "Analog Tom 5"),
+	(49, "Analog Cymbal"),
+	(50, "Analog Tom 6"),
+	(56, "Analog Cowbell"),
+	(62, "Analog Conga H"),
+	(63, "Analog Conga M"),
+	(64, "Analog Conga L"),
+	(70, "Analog Maracas"),
+	(75, "Analog Claves"),
+]};
+
+static XG_JAZZ: NoteMap = NoteMap { base: Some(&XG_STANDARD), notes: &[
+	(36, "Bass Drum Jazz"),
+	(38, "Snare Jazz L"),
+	(40, "Snare Jazz M"),
+	(41, "Jazz Tom 1"),
+	(43, "Jazz Tom 2"),
+	(45, "Jazz Tom 3"),
+	(47, "Jazz Tom 4"),
+	(48, "Jazz Tom 5"),
+	(50, "Jazz Tom 6"),
+]};
+
+static XG_BRUSH: NoteMap = NoteMap { base: Some(&XG_JAZZ), notes: &[
+	(31, "Brush Slap L"),
+	(38, "Brush Slap"),
+	(40, "Brush Tap"),
+	(41, "Brush Tom 1"),
+	(43, "Brush Tom 2"),
+	(45, "Brush Tom 3"),
+	(47, "Brush Tom 4"),
+	(48, "Brush Tom 5"),
+	(50, "Brush Tom 6"),
+]};
+
+// Uses the jazz toms.
+static XG_SYMPHONY: NoteMap = NoteMap { base: Some(&XG_JAZZ), notes: &[
+	(35, "Gran Cassa"),
+	(36, "Gran Cassa Mute"),
+	(38, "Marching Snare M"),
+	(40, "Marching Snare H"),
+	(49, "Hand Cymbal L"),
+	(51, "Hand Cymbal Short L"),
+	(57, "Hand Cymbal H"),
+	(59, "Hand Cymbal Short H"),
+]};
+
+static XG_SFX1: NoteMap = NoteMap { base: None, notes: &[
+	(36, "Cutting Noise"),
+	(37, "Cutting Noise 2"),
+	(39, "String Slap"),
+	(41, "Flute Key Click"),
+	(52, "Shower"),
+	(53, "Thunder"),
+	(54, "Wind"),
+	(55, "Stream"),
+	(56, "Bubble"),
+	(57, "Feed"),
+	(68, "Dog"),
+	(69, "Horse Gallop"),
+	(70, "Bird Tweet 2"),
+	(74, "Ghost"),
+	(75, "Maou"),
+]};
+
+static XG_SFX2: NoteMap = NoteMap { base: None, notes: &[
+	(36, "Dial Tone"),
+	(37, "Door Creaking"),
+	(38, "Door Slam"),
+	(39, "Scratch"),
+	(40, "Scratch 2"),
+	(41, "Windchime"),
+	(42, "Telephone Ring 2"),
+	(52, "Car Engine Ignition"),
+	(53, "Car Tires Squeal"),
+	(54, "Car Passing"),
+	(55, "Car Crash"),
+	(56, "Siren"),
+	(57, "Train"),
+	(58, "Jet Plane"),
+	(59, "Starship"),
+	(60, "Burst Noise"),
+	(61, "Coaster"),
+	(62, "Submarine"),
+	(68, "Laugh"),
+	(69, "Scream"),
+	(70, "Punch"),
+	(71, "Heartbeat"),
+	(72, "Footsteps"),
+	(84, "Machine Gun"),
+	(85, "Laser Gun"),
+	(86, "Explosion"),
+	(87, "Firework"),
+]};
+
+// The note map of a kit, as played on a GS or an XG module, or None for
+// XG kits beyond those of the MU80.
+pub fn note_map(kit: &Voice, xg: bool) -> Option<&'static NoteMap> {
+	match (kit.msb, xg, kit.prg) {
+		(127, true, 1) => Some(&XG_STANDARD),
+		(127, true, 9) => Some(&XG_ROOM),
+		(127, true, 17) => Some(&XG_ROCK),
+		(127, true, 25) => Some(&XG_ELECTRO),
+		(127, true, 26) => Some(&XG_ANALOG),
+		(127, true, 33) => Some(&XG_JAZZ),
+		(127, true, 41) => Some(&XG_BRUSH),
+		(127, true, 49) => Some(&XG_SYMPHONY),
+		(126, true, 1) => Some(&XG_SFX1),
+		(126, true, 2) => Some(&XG_SFX2),
+		(127, false, 9) => Some(&GS_ROOM),
+		(127, false, 17) => Some(&GS_POWER),
+		(127, false, 25) => Some(&GS_ELECTRONIC),
+		(127, false, 26) => Some(&GS_TR808),
+		(127, false, 33) => Some(&GS_JAZZ),
+		(127, false, 41) => Some(&GS_BRUSH),
+		(127, false, 49) => Some(&GS_ORCHESTRA),
+		(127, false, _) => Some(&GS_STANDARD),
+		_ => None,
+	}
+}
@@ -0,0 +1,209 @@
+/*
+ * Reconstructs the part setup of an XG module from bulk dumps of its Multi
+ * Part and Drum Setup blocks, as saved to .syx files by librarians.
+ *
+ * A .syx file is nothing but System Exclusive messages back to back. Parameter
+ * changes and XG System On are applied too, as the module would. Messages
+ * with a wrong checksum or byte count are reported and skipped, like the
+ * module ignores them.
+ */
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::identity::{self, Detection};
+use super::midi::{Selection, DRUM_CHANNEL, DRUM_MSB};
+use super::models::{Model, Resolution};
+use super::sysex::{self, Address, Hex, MultiPart, XgMessage};
+use super::xgmap::{self, Block, Format};
+
+pub struct Part {
+	// 0-based, like the address of the part's block.
+	pub number: u8,
+	pub channel: u8,
+	pub mode: u8,
+	pub sel: Selection,
+	// Whether any message set a parameter of this part.
+	pub touched: bool,
+}
+
+impl Part {
+	// The power-on state of the 0-based `number`.
+	pub fn new(number: u8) -> Part {
+		let drums = number % 16 == DRUM_CHANNEL;
+		Part {
+			number,
+			channel: number,
+			mode: if drums { sysex::PART_MODE_DRUM } else { sysex::PART_MODE_NORMAL },
+			sel: Selection { msb: if drums { DRUM_MSB } else { 0 }, lsb: 0, prg: 1 },
+			touched: false,
+		}
+	}
+}
+
+pub struct Dump {
+	pub detection: Option<Detection>,
+	pub parts: Vec<Part>,
+	// Notes dumped for each drum setup.
+	pub drum_notes: [BTreeSet<u8>; xgmap::DRUM_SETUPS as usize],
+	pub messages: usize,
+	pub warnings: Vec<String>,
+}
+
+fn default_parts() -> Vec<Part> {
+	(0..xgmap::MULTI_PARTS).map(Part::new).collect()
+}
+
+// Splits `bytes` into messages including F0 and F7, warning about bytes
+// between them and messages cut short.
+fn split(bytes: &[u8], warnings: &mut Vec<String>) -> Vec<Vec<u8>> {
+	let mut ret = Vec::new();
+	let mut pos = 0;
+	while pos < bytes.len() {
+		let start = match bytes[pos..].iter().position(|&b| b == 0xF0) {
+			Some(n) => pos + n,
+			None => bytes.len(),
+		};
+		if start > pos {
+			warnings.push(format!("skipped {} bytes at offset {}", start - pos, pos));
+		}
+		if start == bytes.len() {
+			break;
+		}
+		// The first status byte after F0 should be its F7.
+		let end = bytes[start + 1..].iter()
+			.position(|&b| b & 0x80 != 0)
+			.map(|n| start + 1 + n);
+		match end {
+			Some(end) if bytes[end] == 0xF7 => {
+				ret.push(bytes[start..=end].to_vec());
+				pos = end + 1;
+			}
+			_ => {
+				let end = end.unwrap_or(bytes.len());
+				warnings.push(format!("message at offset {} has no F7", start));
+				pos = end;
+			}
+		}
+	}
+	ret
+}
+
+impl Dump {
+	pub fn parse(bytes: &[u8]) -> Dump {
+		let mut ret = Dump {
+			detection: None,
+			parts: default_parts(),
+			drum_notes: Default::default(),
+			messages: 0,
+			warnings: Vec::new(),
+		};
+		for message in split(bytes, &mut ret.warnings) {
+			ret.messages += 1;
+			ret.message(&message);
+		}
+		ret
+	}
+
+	// Handles a message including F0 and F7.
+	fn message(&mut self, sysex: &[u8]) {
+		// An Identity Reply names the model; a dump header only its family.
+		if self.detection.as_ref().is_none_or(|d| d.model.is_none()) {
+			if let Ok(detection) = identity::detect(sysex) {
+				if detection.model.is_some() || self.detection.is_none() {
+					self.detection = Some(detection);
+				}
+			}
+		}
+		match sysex::parse_xg(sysex) {
+			Some(Ok((_, XgMessage::ParameterChange(address, data))))
+			| Some(Ok((_, XgMessage::BulkDump(address, data)))) => self.params(address, data),
+			Some(Err(problem)) => {
+				let head = &sysex[..sysex.len().min(9)];
+				self.warnings.push(format!(
+					"skipped message {} ({} ...): {}", self.messages, Hex(head), problem
+				));
+			}
+			_ => {}
+		}
+	}
+
+	// Applies `data` written to consecutive parameters from `address`.
+	fn params(&mut self, address: Address, data: &[u8]) {
+		if address.low as usize + data.len() > 0x80 {
+			self.warnings.push(format!(
+				"{} bytes from {} run past the end of the block", data.len(), address
+			));
+			return;
+		}
+		match Block::of(address) {
+			Some(Block::System) => {
+				let written = address.low as usize..address.low as usize + data.len();
+				let reset = xgmap::SYSTEM.iter()
+					.any(|p| p.format == Format::Trigger && written.contains(&(p.offset as usize)));
+				if reset {
+					self.parts = default_parts();
+				}
+			}
+			Some(Block::MultiPart(part)) => {
+				let part = &mut self.parts[part as usize];
+				part.touched = true;
+				for (i, &v) in data.iter().enumerate() {
+					match address.low as usize + i {
+						o if o == MultiPart::BankMsb as usize => part.sel.msb = v,
+						o if o == MultiPart::BankLsb as usize => part.sel.lsb = v,
+						o if o == MultiPart::Program as usize => part.sel.prg = v + 1,
+						o if o == MultiPart::PartMode as usize => part.mode = v,
+						o if o == MultiPart::RcvChannel as usize => part.channel = v,
+						_ => {}
+					}
+				}
+			}
+			Some(Block::DrumSetup(setup, note)) => {
+				self.drum_notes[setup as usize].insert(note);
+			}
+			Some(Block::Effect) => {}
+			None => self.warnings.push(format!("unknown XG address {}", address)),
+		}
+	}
+
+	// The parts that the dump set, or None if it has no Multi Part data.
+	pub fn dumped_parts(&self) -> Option<Vec<&Part>> {
+		let ret: Vec<&Part> = self.parts.iter().filter(|p| p.touched).collect();
+		if ret.is_empty() { None } else { Some(ret) }
+	}
+}
+
+// A table of parts, with their voices resolved on a model.
+pub struct PartTable<'a> {
+	pub parts: Vec<&'a Part>,
+	pub model: &'a Model,
+}
+
+fn channel(channel: u8) -> String {
+	match channel {
+		0..=15 => format!("A{}", channel + 1),
+		16..=31 => format!("B{}", channel - 15),
+		_ => "off".to_string(),
+	}
+}
+
+impl<'a> fmt::Display for PartTable<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Part Ch   Mode           Bank-Prg     Voice")?;
+		for part in &self.parts {
+			let voice = match self.model.resolve(part.sel.msb, part.sel.lsb, part.sel.prg) {
+				Resolution::Exact(v) => v.name().to_string(),
+				Resolution::Fallback(v) => {
+					let to = Selection { msb: v.msb, lsb: v.lsb, prg: v.prg };
+					format!("{} (falls back to {})", v.name(), to)
+				}
+				Resolution::Missing => format!("(not on {})", self.model.name),
+			};
+			let mode = Format::PartMode.display(part.mode as u32);
+			writeln!(f, "{:>4} {:<4} {:<14} {}  {}",
+				part.number + 1, channel(part.channel), mode, part.sel, voice)?;
+		}
+		Ok(())
+	}
+}
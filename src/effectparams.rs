@@ -0,0 +1,516 @@
+/*
+ * Parameters of the XG effect types.
+ *
+ * Each effect algorithm (type MSB) has up to 16 parameters, whose raw values
+ * map to engineering units through the conversion tables of the XG
+ * specification. Where a table follows a simple rule, the rule is used
+ * instead of the table.
+ */
+
+use std::fmt;
+
+use self::Unit::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Unit {
+	Number,
+	// Centered on 64.
+	Signed,
+	Decibels,
+	// Tenths of the unit, as in 0.1 ms.
+	Tenths(&'static str),
+	// 0.3 to 30.0 s.
+	ReverbTime,
+	// Initial and reverb delays, 0.1 to 200.0 ms.
+	Delay,
+	// 0.00 to about 40 Hz.
+	LfoFrequency,
+	// Index into `EQ_FREQUENCIES`.
+	EqFrequency,
+	// Like `EqFrequency`, with the lowest or highest value disabling the
+	// filter.
+	HighPass,
+	LowPass,
+	// 3 degrees per step, centered on 64.
+	Phase,
+	// As in "D63>W", "D=W" and "D<W63", centered on 64.
+	Balance(char, char),
+	Choice(&'static [&'static str]),
+}
+
+// Frequencies of the EQ bands and filters, in Hz.
+const EQ_FREQUENCIES: [u32; 61] = [
+	20, 22, 25, 28, 32, 36, 40, 45, 50, 56, 63, 70, 80, 90, 100, 110,
+	125, 140, 160, 180, 200, 225, 250, 280, 315, 355, 400, 450, 500, 560, 630, 700,
+	800, 900, 1000, 1100, 1200, 1400, 1600, 1800, 2000, 2200, 2500, 2800, 3200, 3600, 4000, 4500,
+	5000, 5600, 6300, 7000, 8000, 9000, 10000, 11000, 12000, 14000, 16000, 18000, 20000,
+];
+
+// Reverb times rise in steps of 0.1 s up to 5 s, then in ever larger steps.
+fn reverb_time(raw: u32) -> f64 {
+	match raw {
+		0..=47 => 0.3 + 0.1 * raw as f64,
+		48..=57 => 5.0 + 0.5 * (raw - 47) as f64,
+		58..=67 => 10.0 + (raw - 57) as f64,
+		68 => 25.0,
+		_ => 30.0,
+	}
+}
+
+// The LFO table is linear up to 64 (2.69 Hz), after which the step roughly
+// doubles every 12 values. This approximates the specification's table,
+// which ends at 39.7 Hz rather than 42 Hz.
+fn lfo_frequency(raw: u32) -> f64 {
+	const STEP: f64 = 2.69 / 64.0;
+	let mut ret = STEP * raw.min(64) as f64;
+	let mut step = STEP;
+	let mut rest = raw.saturating_sub(64);
+	while rest > 0 {
+		step *= 2.0;
+		let n = rest.min(12);
+		ret += step * n as f64;
+		rest -= n;
+	}
+	ret
+}
+
+fn frequency(hz: u32) -> String {
+	if hz < 1000 {
+		format!("{} Hz", hz)
+	} else {
+		format!("{:.1} kHz", hz as f64 / 1000.0)
+	}
+}
+
+impl Unit {
+	// The value in the unit's base unit (s, ms, Hz, dB, degrees), if it is
+	// a number.
+	pub fn value(self, raw: u32) -> Option<f64> {
+		let signed = raw as f64 - 64.0;
+		match self {
+			Number => Some(raw as f64),
+			Signed | Decibels => Some(signed),
+			Tenths(_) => Some(raw as f64 / 10.0),
+			ReverbTime => Some(reverb_time(raw)),
+			Delay => Some(0.1 + raw as f64 * 199.9 / 127.0),
+			LfoFrequency => Some(lfo_frequency(raw)),
+			HighPass if raw == 0 => None,
+			LowPass if raw as usize == EQ_FREQUENCIES.len() - 1 => None,
+			EqFrequency | HighPass | LowPass => {
+				EQ_FREQUENCIES.get(raw as usize).map(|&hz| hz as f64)
+			}
+			Phase => Some(signed * 3.0),
+			Balance(..) | Choice(_) => None,
+		}
+	}
+
+	// `raw` as shown on the module, as in "2.5 s" or "D<W20".
+	pub fn display(self, raw: u32) -> String {
+		let value = match self.value(raw) {
+			Some(value) => value,
+			None => {
+				return match self {
+					HighPass | LowPass if raw < 128 => "Thru".to_string(),
+					Balance(a, b) => match raw {
+						64 => format!("{}={}", a, b),
+						0..=63 => format!("{}{}>{}", a, 64 - raw, b),
+						_ => format!("{}<{}{}", a, b, raw - 64),
+					},
+					Choice(names) => names.get(raw as usize)
+						.map_or_else(|| raw.to_string(), |name| name.to_string()),
+					_ => raw.to_string(),
+				};
+			}
+		};
+		match self {
+			Number => raw.to_string(),
+			Signed => format!("{:+}", value),
+			Decibels => format!("{:+} dB", value),
+			Tenths("") => format!("{:.1}", value),
+			Tenths(unit) => format!("{:.1} {}", value, unit),
+			ReverbTime => format!("{:.1} s", value),
+			Delay => format!("{:.1} ms", value),
+			LfoFrequency => format!("{:.2} Hz", value),
+			EqFrequency | HighPass | LowPass => frequency(value as u32),
+			Phase => format!("{:+} deg", value),
+			Balance(..) | Choice(_) => unreachable!(),
+		}
+	}
+
+	// The raw value in `min..=max` for text like "2.5 s", "1.2k", "D=W" or
+	// "Thru". Numbers are rounded to the closest value the module has.
+	pub fn parse(self, text: &str, min: u32, max: u32) -> Option<u32> {
+		let squash = |s: &str| s.split_whitespace().collect::<String>().to_lowercase();
+		let text = squash(text);
+		if let Some(raw) = (min..=max).find(|&raw| squash(&self.display(raw)) == text) {
+			return Some(raw);
+		}
+		let end = text.find(|c: char| !"+-.0123456789".contains(c)).unwrap_or(text.len());
+		let mut number: f64 = text[..end].parse().ok()?;
+		if text[end..].starts_with('k') {
+			number *= 1000.0;
+		}
+		(min..=max)
+			.filter_map(|raw| self.value(raw).map(|value| (raw, (value - number).abs())))
+			.min_by(|a, b| a.1.total_cmp(&b.1))
+			.map(|(raw, _)| raw)
+	}
+}
+
+pub struct EffectParam {
+	// 1-16.
+	pub number: u8,
+	pub name: &'static str,
+	pub min: u32,
+	pub max: u32,
+	pub unit: Unit,
+}
+
+const fn p(number: u8, name: &'static str, min: u32, max: u32, unit: Unit) -> EffectParam {
+	EffectParam { number, name, min, max, unit }
+}
+
+impl EffectParam {
+	pub fn display(&self, raw: u32) -> String {
+		self.unit.display(raw)
+	}
+
+	pub fn parse(&self, text: &str) -> Option<u32> {
+		self.unit.parse(text, self.min, self.max)
+	}
+}
+
+impl fmt::Display for EffectParam {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:>2} {}: {} to {}",
+			self.number, self.name, self.display(self.min), self.display(self.max)
+		)
+	}
+}
+
+// Parameters shared by many algorithms.
+const DRY_WET: Unit = Balance('D', 'W');
+const fn dry_wet(number: u8) -> EffectParam {
+	p(number, "Dry/Wet", 1, 127, DRY_WET)
+}
+const fn feedback(number: u8, name: &'static str) -> EffectParam {
+	p(number, name, 1, 127, Signed)
+}
+const fn high_damp(number: u8) -> EffectParam {
+	p(number, "High Damp", 1, 10, Tenths(""))
+}
+const fn lfo(number: u8) -> EffectParam {
+	p(number, "LFO Frequency", 0, 127, LfoFrequency)
+}
+const fn gain(number: u8, name: &'static str) -> EffectParam {
+	p(number, name, 52, 76, Decibels)
+}
+const fn hpf(number: u8) -> EffectParam {
+	p(number, "HPF Cutoff", 0, 52, HighPass)
+}
+const fn lpf(number: u8) -> EffectParam {
+	p(number, "LPF Cutoff", 34, 60, LowPass)
+}
+
+const REVERB: &[EffectParam] = &[
+	p(1, "Reverb Time", 0, 69, ReverbTime),
+	p(2, "Diffusion", 0, 10, Number),
+	p(3, "Initial Delay", 0, 63, Delay),
+	hpf(4),
+	lpf(5),
+	dry_wet(11),
+	p(12, "Rev Delay", 0, 63, Delay),
+	p(13, "Density", 0, 3, Number),
+	p(14, "Er/Rev Balance", 1, 127, Balance('E', 'R')),
+	feedback(16, "Feedback Level"),
+];
+
+// White Room, Tunnel and Basement, which also model the room's dimensions.
+const ROOM_SIMULATION: &[EffectParam] = &[
+	p(1, "Reverb Time", 0, 69, ReverbTime),
+	p(2, "Diffusion", 0, 10, Number),
+	p(3, "Initial Delay", 0, 63, Delay),
+	hpf(4),
+	lpf(5),
+	p(6, "Width", 0, 37, Number),
+	p(7, "Height", 0, 73, Number),
+	p(8, "Depth", 0, 104, Number),
+	p(9, "Wall Vary", 0, 30, Number),
+	dry_wet(11),
+	p(12, "Rev Delay", 0, 63, Delay),
+	p(13, "Density", 0, 4, Number),
+	p(14, "Er/Rev Balance", 1, 127, Balance('E', 'R')),
+	feedback(16, "Feedback Level"),
+];
+
+// Delays are in steps of 0.1 ms.
+const fn delay(number: u8, name: &'static str, max: u32) -> EffectParam {
+	p(number, name, 1, max, Tenths("ms"))
+}
+
+// The EQ bands that most delays and modulation effects share.
+const fn eq_low(number: u8) -> EffectParam {
+	p(number, "EQ Low Frequency", 8, 40, EqFrequency)
+}
+const fn eq_high(number: u8) -> EffectParam {
+	p(number, "EQ High Frequency", 28, 58, EqFrequency)
+}
+
+const DELAY_LCR: &[EffectParam] = &[
+	delay(1, "Lch Delay", 7150),
+	delay(2, "Rch Delay", 7150),
+	delay(3, "Cch Delay", 7150),
+	delay(4, "Feedback Delay", 7150),
+	feedback(5, "Feedback Level"),
+	p(6, "Cch Level", 0, 127, Number),
+	high_damp(7),
+	dry_wet(11),
+	eq_low(13),
+	gain(14, "EQ Low Gain"),
+	eq_high(15),
+	gain(16, "EQ High Gain"),
+];
+
+const DELAY_LR: &[EffectParam] = &[
+	delay(1, "Lch Delay", 7150),
+	delay(2, "Rch Delay", 7150),
+	delay(3, "Feedback Delay 1", 7150),
+	delay(4, "Feedback Delay 2", 7150),
+	feedback(5, "Feedback Level"),
+	high_damp(6),
+	dry_wet(11),
+	eq_low(13),
+	gain(14, "EQ Low Gain"),
+	eq_high(15),
+	gain(16, "EQ High Gain"),
+];
+
+const ECHO: &[EffectParam] = &[
+	delay(1, "Lch Delay 1", 3550),
+	feedback(2, "Lch Feedback Level"),
+	delay(3, "Rch Delay 1", 3550),
+	feedback(4, "Rch Feedback Level"),
+	high_damp(5),
+	delay(6, "Lch Delay 2", 3550),
+	delay(7, "Rch Delay 2", 3550),
+	p(8, "Delay 2 Level", 0, 127, Number),
+	dry_wet(11),
+	eq_low(13),
+	gain(14, "EQ Low Gain"),
+	eq_high(15),
+	gain(16, "EQ High Gain"),
+];
+
+const CROSS_DELAY: &[EffectParam] = &[
+	delay(1, "L>R Delay", 3550),
+	delay(2, "R>L Delay", 3550),
+	feedback(3, "Feedback Level"),
+	p(4, "Input Select", 0, 2, Choice(&["L", "R", "L&R"])),
+	high_damp(5),
+	dry_wet(11),
+	eq_low(13),
+	gain(14, "EQ Low Gain"),
+	eq_high(15),
+	gain(16, "EQ High Gain"),
+];
+
+const EARLY_REFLECTION: &[EffectParam] = &[
+	p(1, "Type", 0, 5, Choice(&["S-H", "L-H", "Random", "Reverse", "Plate", "Spring"])),
+	p(2, "Room Size", 0, 44, Number),
+	p(3, "Diffusion", 0, 10, Number),
+	p(4, "Initial Delay", 0, 63, Delay),
+	feedback(5, "Feedback Level"),
+	hpf(6),
+	lpf(7),
+	dry_wet(11),
+	p(12, "Liveness", 0, 10, Number),
+	p(13, "Density", 0, 3, Number),
+	high_damp(14),
+];
+
+const GATE_REVERB: &[EffectParam] = &[
+	p(1, "Type", 0, 1, Choice(&["Type A", "Type B"])),
+	p(2, "Room Size", 0, 44, Number),
+	p(3, "Diffusion", 0, 10, Number),
+	p(4, "Initial Delay", 0, 63, Delay),
+	feedback(5, "Feedback Level"),
+	hpf(6),
+	lpf(7),
+	dry_wet(11),
+	p(12, "Liveness", 0, 10, Number),
+	p(13, "Density", 0, 3, Number),
+	high_damp(14),
+];
+
+const KARAOKE: &[EffectParam] = &[
+	p(1, "Delay Time", 0, 127, Delay),
+	feedback(2, "Feedback Level"),
+	hpf(3),
+	lpf(4),
+	dry_wet(11),
+];
+
+const CHORUS: &[EffectParam] = &[
+	lfo(1),
+	p(2, "LFO PM Depth", 0, 127, Number),
+	feedback(3, "Feedback Level"),
+	p(4, "Delay Offset", 0, 127, Number),
+	eq_low(6),
+	gain(7, "EQ Low Gain"),
+	eq_high(8),
+	gain(9, "EQ High Gain"),
+	dry_wet(10),
+];
+
+const FLANGER: &[EffectParam] = &[
+	lfo(1),
+	p(2, "LFO Depth", 0, 127, Number),
+	feedback(3, "Feedback Level"),
+	p(4, "Delay Offset", 0, 63, Number),
+	eq_low(6),
+	gain(7, "EQ Low Gain"),
+	eq_high(8),
+	gain(9, "EQ High Gain"),
+	dry_wet(10),
+	p(14, "LFO Phase Difference", 4, 124, Phase),
+];
+
+const SYMPHONIC: &[EffectParam] = &[
+	lfo(1),
+	p(2, "LFO Depth", 0, 127, Number),
+	p(3, "Delay Offset", 0, 127, Number),
+	eq_low(6),
+	gain(7, "EQ Low Gain"),
+	eq_high(8),
+	gain(9, "EQ High Gain"),
+	dry_wet(10),
+];
+
+const ROTARY_SPEAKER: &[EffectParam] = &[
+	lfo(1),
+	p(2, "LFO Depth", 0, 127, Number),
+	eq_low(6),
+	gain(7, "EQ Low Gain"),
+	eq_high(8),
+	gain(9, "EQ High Gain"),
+	dry_wet(10),
+];
+
+const TREMOLO: &[EffectParam] = &[
+	lfo(1),
+	p(2, "AM Depth", 0, 127, Number),
+	p(3, "PM Depth", 0, 127, Number),
+	eq_low(6),
+	gain(7, "EQ Low Gain"),
+	eq_high(8),
+	gain(9, "EQ High Gain"),
+	p(14, "LFO Phase Difference", 4, 124, Phase),
+	p(15, "Input Mode", 0, 1, Choice(&["Mono", "Stereo"])),
+];
+
+const AUTO_PAN: &[EffectParam] = &[
+	lfo(1),
+	p(2, "L/R Depth", 0, 127, Number),
+	p(3, "F/R Depth", 0, 127, Number),
+	p(4, "Pan Direction", 0, 5, Choice(&["L<->R", "L->R", "L<-R", "L turn", "R turn", "L/R"])),
+	eq_low(6),
+	gain(7, "EQ Low Gain"),
+	eq_high(8),
+	gain(9, "EQ High Gain"),
+];
+
+const PHASER: &[EffectParam] = &[
+	lfo(1),
+	p(2, "LFO Depth", 0, 127, Number),
+	p(3, "Phase Shift Offset", 0, 127, Number),
+	feedback(4, "Feedback Level"),
+	dry_wet(10),
+	p(11, "Stage", 6, 10, Number),
+	p(12, "Diffusion", 0, 1, Choice(&["Mono", "Stereo"])),
+	p(13, "LFO Phase Difference", 4, 124, Phase),
+];
+
+const DISTORTION: &[EffectParam] = &[
+	p(1, "Drive", 0, 127, Number),
+	p(2, "EQ Low Frequency", 8, 40, EqFrequency),
+	gain(3, "EQ Low Gain"),
+	lpf(4),
+	p(5, "Output Level", 0, 127, Number),
+	p(7, "EQ Mid Frequency", 14, 54, EqFrequency),
+	gain(8, "EQ Mid Gain"),
+	p(9, "EQ Mid Width", 10, 120, Tenths("")),
+	dry_wet(10),
+	p(11, "Edge (Clip Curve)", 0, 127, Number),
+];
+
+const AMP_SIMULATOR: &[EffectParam] = &[
+	p(1, "Drive", 0, 127, Number),
+	p(2, "Amp Type", 0, 3, Choice(&["Off", "Stack", "Combo", "Tube"])),
+	lpf(3),
+	p(4, "Output Level", 0, 127, Number),
+	dry_wet(10),
+	p(11, "Edge (Clip Curve)", 0, 127, Number),
+];
+
+const EQ3: &[EffectParam] = &[
+	gain(1, "EQ Low Gain"),
+	p(2, "EQ Mid Frequency", 14, 54, EqFrequency),
+	gain(3, "EQ Mid Gain"),
+	p(4, "EQ Mid Width", 10, 120, Tenths("")),
+	gain(5, "EQ High Gain"),
+	p(6, "EQ Low Frequency", 4, 40, EqFrequency),
+	p(7, "EQ High Frequency", 28, 58, EqFrequency),
+];
+
+const EQ2: &[EffectParam] = &[
+	p(1, "EQ Low Frequency", 4, 40, EqFrequency),
+	gain(2, "EQ Low Gain"),
+	p(3, "EQ High Frequency", 28, 58, EqFrequency),
+	gain(4, "EQ High Gain"),
+];
+
+const AUTO_WAH: &[EffectParam] = &[
+	lfo(1),
+	p(2, "LFO Depth", 0, 127, Number),
+	p(3, "Cutoff Frequency Offset", 0, 127, Number),
+	p(4, "Resonance", 10, 120, Tenths("")),
+	p(6, "EQ Low Frequency", 4, 40, EqFrequency),
+	gain(7, "EQ Low Gain"),
+	p(8, "EQ High Frequency", 28, 58, EqFrequency),
+	gain(9, "EQ High Gain"),
+	dry_wet(10),
+];
+
+// Parameters of the algorithm selected by a type MSB. No Effect and Thru
+// have none.
+pub fn params(msb: u8) -> &'static [EffectParam] {
+	match msb {
+		0x01..=0x04 => REVERB,
+		0x05 => DELAY_LCR,
+		0x06 => DELAY_LR,
+		0x07 => ECHO,
+		0x08 => CROSS_DELAY,
+		0x09 => EARLY_REFLECTION,
+		0x0A | 0x0B => GATE_REVERB,
+		0x10 | 0x11 | 0x13 => ROOM_SIMULATION,
+		0x14 => KARAOKE,
+		0x41 | 0x42 => CHORUS,
+		0x43 => FLANGER,
+		0x44 => SYMPHONIC,
+		0x45 => ROTARY_SPEAKER,
+		0x46 => TREMOLO,
+		0x47 => AUTO_PAN,
+		0x48 => PHASER,
+		0x49 | 0x4A => DISTORTION,
+		0x4B => AMP_SIMULATOR,
+		0x4C => EQ3,
+		0x4D => EQ2,
+		0x4E => AUTO_WAH,
+		_ => &[],
+	}
+}
+
+// `number` is 1-16.
+pub fn param(msb: u8, number: u8) -> Option<&'static EffectParam> {
+	params(msb).iter().find(|p| p.number == number)
+}
@@ -0,0 +1,281 @@
+/*
+ * XG effect types.
+ *
+ * Effect types are selected with two bytes: the MSB picks the algorithm and
+ * the LSB one of its presets. An LSB that a module doesn't know selects the
+ * LSB 0 preset of the same algorithm.
+ *
+ * Like voices, effect types come in sets, and a model lists the sets that
+ * it has.
+ */
+
+#![allow(non_upper_case_globals)]
+
+use std::fmt;
+use std::ops;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EffectBlock {
+	Reverb,
+	Chorus,
+	Variation,
+	Insertion,
+}
+
+pub const EFFECT_BLOCKS: [EffectBlock; 4] = [
+	EffectBlock::Reverb,
+	EffectBlock::Chorus,
+	EffectBlock::Variation,
+	EffectBlock::Insertion,
+];
+
+impl fmt::Display for EffectBlock {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.pad(match *self {
+			EffectBlock::Reverb => "Reverb",
+			EffectBlock::Chorus => "Chorus",
+			EffectBlock::Variation => "Variation",
+			EffectBlock::Insertion => "Insertion",
+		})
+	}
+}
+
+impl EffectBlock {
+	// Stable lowercase identifier, for use in exported data.
+	pub fn id(self) -> &'static str {
+		match self {
+			EffectBlock::Reverb => "reverb",
+			EffectBlock::Chorus => "chorus",
+			EffectBlock::Variation => "variation",
+			EffectBlock::Insertion => "insertion",
+		}
+	}
+
+	// All built-in types of this block.
+	pub fn types(self) -> impl Iterator<Item = &'static EffectType> {
+		EFFECTSETS.iter().flat_map(|set| set.0.iter()).filter(move |t| t.block == self)
+	}
+
+	// Searches all built-in sets, for when the model doesn't matter.
+	pub fn find(self, msb: u8, lsb: u8) -> Option<&'static EffectType> {
+		self.types().find(|t| t.msb == msb && t.lsb == lsb)
+	}
+}
+
+impl FromStr for EffectBlock {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<EffectBlock, String> {
+		EFFECT_BLOCKS.iter()
+			.find(|b| b.id() == s)
+			.cloned()
+			.ok_or_else(|| format!(
+				"unknown effect block \"{}\", valid ones are reverb, chorus, variation, insertion", s
+			))
+	}
+}
+
+pub struct EffectType {
+	pub block: EffectBlock,
+	pub msb: u8,
+	pub lsb: u8,
+	pub name: &'static str,
+}
+
+impl fmt::Display for EffectType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:<9} {:03}-{:03} {}", self.block, self.msb, self.lsb, self.name)
+	}
+}
+
+pub struct EffectTypes(pub &'static [EffectType]);
+
+#[derive(Default)]
+pub struct EffectCounts {
+	pub reverb: u16,
+	pub chorus: u16,
+	pub variation: u16,
+	pub insertion: u16,
+}
+
+impl EffectCounts {
+	pub fn total(&self) -> u16 {
+		self.reverb + self.chorus + self.variation + self.insertion
+	}
+}
+
+impl ops::AddAssign for EffectCounts {
+	fn add_assign(&mut self, other: EffectCounts) {
+		self.reverb += other.reverb;
+		self.chorus += other.chorus;
+		self.variation += other.variation;
+		self.insertion += other.insertion;
+	}
+}
+
+impl fmt::Display for EffectCounts {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f,
+			"Total number of effect types: {} ({} reverb, {} chorus, {} variation, {} insertion)",
+			self.total(), self.reverb, self.chorus, self.variation, self.insertion
+		)
+	}
+}
+
+impl EffectTypes {
+	// Turns effect types created at runtime into a set that can be used
+	// alongside the static ones. The set lives until the program exits.
+	pub fn leak(types: Vec<EffectType>) -> &'static EffectTypes {
+		Box::leak(Box::new(EffectTypes(Box::leak(types.into_boxed_slice()))))
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &'static EffectType> {
+		self.0.iter()
+	}
+
+	pub fn find(&self, block: EffectBlock, msb: u8, lsb: u8) -> Option<&'static EffectType> {
+		self.iter().find(|t| t.block == block && t.msb == msb && t.lsb == lsb)
+	}
+
+	pub fn count(&self) -> EffectCounts {
+		let mut ret: EffectCounts = Default::default();
+		for it in self.0 {
+			match it.block {
+				EffectBlock::Reverb => ret.reverb += 1,
+				EffectBlock::Chorus => ret.chorus += 1,
+				EffectBlock::Variation => ret.variation += 1,
+				EffectBlock::Insertion => ret.insertion += 1,
+			}
+		}
+		ret
+	}
+}
+
+const fn reverb(msb: u8, lsb: u8, name: &'static str) -> EffectType {
+	EffectType { block: EffectBlock::Reverb, msb, lsb, name }
+}
+
+const fn chorus(msb: u8, lsb: u8, name: &'static str) -> EffectType {
+	EffectType { block: EffectBlock::Chorus, msb, lsb, name }
+}
+
+const fn variation(msb: u8, lsb: u8, name: &'static str) -> EffectType {
+	EffectType { block: EffectBlock::Variation, msb, lsb, name }
+}
+
+const fn insertion(msb: u8, lsb: u8, name: &'static str) -> EffectType {
+	EffectType { block: EffectBlock::Insertion, msb, lsb, name }
+}
+
+// XG Level 1 (1994)
+pub const XGLevel1Effects: &EffectTypes = &EffectTypes(&[
+	reverb(0x00, 0x00, "No Effect"),
+	reverb(0x01, 0x00, "Hall 1"),
+	reverb(0x01, 0x01, "Hall 2"),
+	reverb(0x02, 0x00, "Room 1"),
+	reverb(0x02, 0x01, "Room 2"),
+	reverb(0x02, 0x02, "Room 3"),
+	reverb(0x03, 0x00, "Stage 1"),
+	reverb(0x03, 0x01, "Stage 2"),
+	reverb(0x04, 0x00, "Plate"),
+	chorus(0x00, 0x00, "No Effect"),
+	chorus(0x41, 0x00, "Chorus 1"),
+	chorus(0x41, 0x01, "Chorus 2"),
+	chorus(0x41, 0x02, "Chorus 3"),
+	chorus(0x42, 0x00, "Celeste 1"),
+	chorus(0x42, 0x01, "Celeste 2"),
+	chorus(0x42, 0x02, "Celeste 3"),
+	chorus(0x43, 0x00, "Flanger 1"),
+	chorus(0x43, 0x01, "Flanger 2"),
+	variation(0x00, 0x00, "No Effect"),
+	variation(0x01, 0x00, "Hall 1"),
+	variation(0x01, 0x01, "Hall 2"),
+	variation(0x02, 0x00, "Room 1"),
+	variation(0x02, 0x01, "Room 2"),
+	variation(0x02, 0x02, "Room 3"),
+	variation(0x03, 0x00, "Stage 1"),
+	variation(0x03, 0x01, "Stage 2"),
+	variation(0x04, 0x00, "Plate"),
+	variation(0x05, 0x00, "Delay L,C,R"),
+	variation(0x06, 0x00, "Delay L,R"),
+	variation(0x07, 0x00, "Echo"),
+	variation(0x08, 0x00, "Cross Delay"),
+	variation(0x09, 0x00, "Early Reflection 1"),
+	variation(0x09, 0x01, "Early Reflection 2"),
+	variation(0x0A, 0x00, "Gate Reverb"),
+	variation(0x0B, 0x00, "Reverse Gate"),
+	variation(0x14, 0x00, "Karaoke 1"),
+	variation(0x14, 0x01, "Karaoke 2"),
+	variation(0x14, 0x02, "Karaoke 3"),
+	variation(0x40, 0x00, "Thru"),
+	variation(0x41, 0x00, "Chorus 1"),
+	variation(0x41, 0x01, "Chorus 2"),
+	variation(0x41, 0x02, "Chorus 3"),
+	variation(0x42, 0x00, "Celeste 1"),
+	variation(0x42, 0x01, "Celeste 2"),
+	variation(0x42, 0x02, "Celeste 3"),
+	variation(0x43, 0x00, "Flanger 1"),
+	variation(0x43, 0x01, "Flanger 2"),
+	variation(0x44, 0x00, "Symphonic"),
+	variation(0x45, 0x00, "Rotary Speaker"),
+	variation(0x46, 0x00, "Tremolo"),
+	variation(0x47, 0x00, "Auto Pan"),
+	variation(0x48, 0x00, "Phaser 1"),
+	variation(0x49, 0x00, "Distortion"),
+	variation(0x4A, 0x00, "Over Drive"),
+	variation(0x4B, 0x00, "Amp Simulator"),
+	variation(0x4C, 0x00, "3-Band EQ"),
+	variation(0x4D, 0x00, "2-Band EQ"),
+	variation(0x4E, 0x00, "Auto Wah"),
+]);
+
+// Types added by the MU80, including its insertion effect block, which
+// offers the variation algorithms that make sense on a single part.
+pub const MU80Effects: &EffectTypes = &EffectTypes(&[
+	reverb(0x10, 0x00, "White Room"),
+	reverb(0x11, 0x00, "Tunnel"),
+	reverb(0x13, 0x00, "Basement"),
+	chorus(0x41, 0x08, "Chorus 4"),
+	chorus(0x42, 0x08, "Celeste 4"),
+	chorus(0x43, 0x08, "Flanger 3"),
+	variation(0x10, 0x00, "White Room"),
+	variation(0x11, 0x00, "Tunnel"),
+	variation(0x13, 0x00, "Basement"),
+	variation(0x41, 0x08, "Chorus 4"),
+	variation(0x42, 0x08, "Celeste 4"),
+	variation(0x43, 0x08, "Flanger 3"),
+	variation(0x48, 0x08, "Phaser 2"),
+	insertion(0x00, 0x00, "No Effect"),
+	insertion(0x05, 0x00, "Delay L,C,R"),
+	insertion(0x06, 0x00, "Delay L,R"),
+	insertion(0x07, 0x00, "Echo"),
+	insertion(0x08, 0x00, "Cross Delay"),
+	insertion(0x40, 0x00, "Thru"),
+	insertion(0x41, 0x00, "Chorus 1"),
+	insertion(0x41, 0x01, "Chorus 2"),
+	insertion(0x41, 0x02, "Chorus 3"),
+	insertion(0x41, 0x08, "Chorus 4"),
+	insertion(0x42, 0x00, "Celeste 1"),
+	insertion(0x42, 0x01, "Celeste 2"),
+	insertion(0x42, 0x02, "Celeste 3"),
+	insertion(0x42, 0x08, "Celeste 4"),
+	insertion(0x43, 0x00, "Flanger 1"),
+	insertion(0x43, 0x01, "Flanger 2"),
+	insertion(0x43, 0x08, "Flanger 3"),
+	insertion(0x44, 0x00, "Symphonic"),
+	insertion(0x45, 0x00, "Rotary Speaker"),
+	insertion(0x46, 0x00, "Tremolo"),
+	insertion(0x47, 0x00, "Auto Pan"),
+	insertion(0x48, 0x00, "Phaser 1"),
+	insertion(0x48, 0x08, "Phaser 2"),
+	insertion(0x49, 0x00, "Distortion"),
+	insertion(0x4A, 0x00, "Over Drive"),
+	insertion(0x4B, 0x00, "Amp Simulator"),
+	insertion(0x4C, 0x00, "3-Band EQ"),
+	insertion(0x4D, 0x00, "2-Band EQ"),
+	insertion(0x4E, 0x00, "Auto Wah"),
+]);
+
+// All built-in sets.
+pub static EFFECTSETS: &[&EffectTypes] = &[XGLevel1Effects, MU80Effects];
@@ -0,0 +1,409 @@
+/*
+ * Conversion of SMFs between Roland GS and Yamaha XG.
+ *
+ * GS selects variation tones with CC0 and ignores CC32 (or uses it to pick
+ * a tone map), while drum kits are selected with plain Program Changes on
+ * parts that were assigned to a rhythm map via SysEx. XG uses CC0/CC32 as
+ * MSB/LSB on all parts, with MSB 127 and 126 selecting drum and SFX kits.
+ */
+
+use std::fmt;
+
+use super::midi::*;
+use super::smf::{Event, Smf, Track, TrackEvent};
+use super::sysex;
+use super::voices::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Standard {
+	Gs,
+	Xg,
+}
+
+// A GS variation tone and its closest XG voice.
+struct Equivalent {
+	gs_var: u8,
+	gs_prg: u8,
+	xg_msb: u8,
+	xg_lsb: u8,
+	xg_prg: u8,
+	gs_name: &'static str,
+}
+
+// GS variation tones of the SC-55 with a direct XG Level 1 equivalent.
+// Capital tones (variation 0) map to the GM voices, and are not listed.
+const EQUIVALENTS: &[Equivalent] = &[
+	Equivalent{gs_var:   8, gs_prg:  15, xg_msb:   0, xg_lsb:  96, xg_prg:  15, gs_name: "Church Bell"},
+	Equivalent{gs_var:   8, gs_prg:  17, xg_msb:   0, xg_lsb:  32, xg_prg:  17, gs_name: "Detuned Or.1"},
+	Equivalent{gs_var:   8, gs_prg:  18, xg_msb:   0, xg_lsb:  32, xg_prg:  18, gs_name: "Detuned Or.2"},
+	Equivalent{gs_var:   8, gs_prg:  20, xg_msb:   0, xg_lsb:  35, xg_prg:  20, gs_name: "Church Org.2"},
+	Equivalent{gs_var:   8, gs_prg:  25, xg_msb:   0, xg_lsb:  96, xg_prg:  25, gs_name: "Ukulele"},
+	Equivalent{gs_var:   8, gs_prg:  26, xg_msb:   0, xg_lsb:  35, xg_prg:  26, gs_name: "12-str.Gt"},
+	Equivalent{gs_var:  16, gs_prg:  26, xg_msb:   0, xg_lsb:  96, xg_prg:  26, gs_name: "Mandolin"},
+	Equivalent{gs_var:   8, gs_prg:  28, xg_msb:   0, xg_lsb:  32, xg_prg:  28, gs_name: "Chorus Gt."},
+	Equivalent{gs_var:   8, gs_prg:  29, xg_msb:   0, xg_lsb:  40, xg_prg:  29, gs_name: "Funk Gt."},
+	Equivalent{gs_var:   8, gs_prg:  31, xg_msb:   0, xg_lsb:  40, xg_prg:  31, gs_name: "Feedback Gt."},
+	Equivalent{gs_var:   8, gs_prg:  32, xg_msb:   0, xg_lsb:  65, xg_prg:  32, gs_name: "Gt. Feedback"},
+	Equivalent{gs_var:   8, gs_prg:  41, xg_msb:   0, xg_lsb:   8, xg_prg:  41, gs_name: "Slow Violin"},
+	Equivalent{gs_var:   8, gs_prg:  49, xg_msb:   0, xg_lsb:  40, xg_prg:  49, gs_name: "Orchestra"},
+	Equivalent{gs_var:   8, gs_prg: 105, xg_msb:   0, xg_lsb:  35, xg_prg: 105, gs_name: "Sitar 2"},
+	Equivalent{gs_var:   8, gs_prg: 108, xg_msb:   0, xg_lsb:  96, xg_prg: 108, gs_name: "Taisho Koto"},
+	Equivalent{gs_var:   8, gs_prg: 116, xg_msb:   0, xg_lsb:  96, xg_prg: 116, gs_name: "Castanets"},
+	Equivalent{gs_var:   1, gs_prg: 121, xg_msb:  64, xg_lsb:   0, xg_prg:   1, gs_name: "Gt.Cut Noise"},
+	Equivalent{gs_var:   2, gs_prg: 121, xg_msb:  64, xg_lsb:   0, xg_prg:   4, gs_name: "String Slap"},
+	Equivalent{gs_var:   1, gs_prg: 122, xg_msb:  64, xg_lsb:   0, xg_prg:  17, gs_name: "Fl.Key Click"},
+	Equivalent{gs_var:   1, gs_prg: 123, xg_msb:  64, xg_lsb:   0, xg_prg:  33, gs_name: "Rain"},
+	Equivalent{gs_var:   2, gs_prg: 123, xg_msb:  64, xg_lsb:   0, xg_prg:  34, gs_name: "Thunder"},
+	Equivalent{gs_var:   3, gs_prg: 123, xg_msb:  64, xg_lsb:   0, xg_prg:  35, gs_name: "Wind"},
+	Equivalent{gs_var:   4, gs_prg: 123, xg_msb:  64, xg_lsb:   0, xg_prg:  36, gs_name: "Stream"},
+	Equivalent{gs_var:   5, gs_prg: 123, xg_msb:  64, xg_lsb:   0, xg_prg:  37, gs_name: "Bubble"},
+	Equivalent{gs_var:   1, gs_prg: 124, xg_msb:  64, xg_lsb:   0, xg_prg:  49, gs_name: "Dog"},
+	Equivalent{gs_var:   2, gs_prg: 124, xg_msb:  64, xg_lsb:   0, xg_prg:  50, gs_name: "Horse-Gallop"},
+	Equivalent{gs_var:   3, gs_prg: 124, xg_msb:  64, xg_lsb:   0, xg_prg:  51, gs_name: "Bird 2"},
+	Equivalent{gs_var:   1, gs_prg: 125, xg_msb:  64, xg_lsb:   0, xg_prg:  71, gs_name: "Telephone 2"},
+	Equivalent{gs_var:   2, gs_prg: 125, xg_msb:  64, xg_lsb:   0, xg_prg:  66, gs_name: "DoorCreaking"},
+	Equivalent{gs_var:   3, gs_prg: 125, xg_msb:  64, xg_lsb:   0, xg_prg:  67, gs_name: "Door"},
+	Equivalent{gs_var:   4, gs_prg: 125, xg_msb:  64, xg_lsb:   0, xg_prg:  68, gs_name: "Scratch"},
+	Equivalent{gs_var:   5, gs_prg: 125, xg_msb:  64, xg_lsb:   0, xg_prg:  70, gs_name: "Windchime"},
+	Equivalent{gs_var:   1, gs_prg: 126, xg_msb:  64, xg_lsb:   0, xg_prg:  81, gs_name: "Car-Engine"},
+	Equivalent{gs_var:   2, gs_prg: 126, xg_msb:  64, xg_lsb:   0, xg_prg:  82, gs_name: "Car-Stop"},
+	Equivalent{gs_var:   3, gs_prg: 126, xg_msb:  64, xg_lsb:   0, xg_prg:  83, gs_name: "Car-Pass"},
+	Equivalent{gs_var:   4, gs_prg: 126, xg_msb:  64, xg_lsb:   0, xg_prg:  84, gs_name: "Car-Crash"},
+	Equivalent{gs_var:   5, gs_prg: 126, xg_msb:  64, xg_lsb:   0, xg_prg:  85, gs_name: "Siren"},
+	Equivalent{gs_var:   6, gs_prg: 126, xg_msb:  64, xg_lsb:   0, xg_prg:  86, gs_name: "Train"},
+	Equivalent{gs_var:   7, gs_prg: 126, xg_msb:  64, xg_lsb:   0, xg_prg:  87, gs_name: "Jetplane"},
+	Equivalent{gs_var:   8, gs_prg: 126, xg_msb:  64, xg_lsb:   0, xg_prg:  88, gs_name: "Starship"},
+	Equivalent{gs_var:   9, gs_prg: 126, xg_msb:  64, xg_lsb:   0, xg_prg:  89, gs_name: "Burst Noise"},
+	Equivalent{gs_var:   1, gs_prg: 127, xg_msb:  64, xg_lsb:   0, xg_prg:  97, gs_name: "Laughing"},
+	Equivalent{gs_var:   2, gs_prg: 127, xg_msb:  64, xg_lsb:   0, xg_prg:  98, gs_name: "Screaming"},
+	Equivalent{gs_var:   3, gs_prg: 127, xg_msb:  64, xg_lsb:   0, xg_prg:  99, gs_name: "Punch"},
+	Equivalent{gs_var:   4, gs_prg: 127, xg_msb:  64, xg_lsb:   0, xg_prg: 100, gs_name: "Heart Beat"},
+	Equivalent{gs_var:   5, gs_prg: 127, xg_msb:  64, xg_lsb:   0, xg_prg: 101, gs_name: "Footsteps"},
+	Equivalent{gs_var:   1, gs_prg: 128, xg_msb:  64, xg_lsb:   0, xg_prg: 113, gs_name: "Machine Gun"},
+	Equivalent{gs_var:   2, gs_prg: 128, xg_msb:  64, xg_lsb:   0, xg_prg: 114, gs_name: "Lasergun"},
+	Equivalent{gs_var:   3, gs_prg: 128, xg_msb:  64, xg_lsb:   0, xg_prg: 115, gs_name: "Explosion"},
+];
+
+// Program number of the GS SFX kit, which XG has at MSB 126 instead.
+const GS_SFX_KIT: u8 = 57;
+
+fn xg_voice(msb: u8, lsb: u8, prg: u8) -> Option<&'static Voice> {
+	[GM, GSDrums, XGLevel1].iter().filter_map(|set| set.find(msb, lsb, prg)).next()
+}
+
+// Part number within GS part addresses, which puts the rhythm part first.
+fn gs_block(channel: u8) -> u8 {
+	match channel {
+		0..=8 => channel + 1,
+		DRUM_CHANNEL => 0,
+		_ => channel,
+	}
+}
+
+// "Use for Rhythm Part" (0 = off, 1 = MAP1, 2 = MAP2).
+fn gs_rhythm_sysex(channel: u8, map: u8) -> Vec<u8> {
+	sysex::gs_data_set(0, [0x40, 0x10 | gs_block(channel), 0x15], &[map])
+}
+
+// Decodes a GS "Use for Rhythm Part" message into channel and map.
+fn parse_gs_rhythm(sysex: &[u8]) -> Option<(u8, u8)> {
+	match *sysex {
+		[0xF0, 0x41, _, 0x42, 0x12, 0x40, block, 0x15, map, ..] if block & 0xF0 == 0x10 => {
+			let channel = match block & 0x0F {
+				0 => DRUM_CHANNEL,
+				b @ 1..=9 => b - 1,
+				b => b,
+			};
+			Some((channel, map))
+		}
+		_ => None,
+	}
+}
+
+// Decodes an XG Multi Part "Part Mode" message into channel and mode.
+// Assumes the default assignment of parts to channels.
+fn parse_xg_part_mode(sysex: &[u8]) -> Option<(u8, u8)> {
+	match *sysex {
+		[0xF0, 0x43, dev, 0x4C, 0x08, part, 0x07, mode, ..] if dev & 0xF0 == 0x10 && part < 16 => {
+			Some((part, mode))
+		}
+		_ => None,
+	}
+}
+
+fn is_gs_reset(sysex: &[u8]) -> bool {
+	sysex.first() == Some(&0xF0) && sysex.get(1) == Some(&0x41) && is_reset(sysex)
+}
+
+fn is_xg_system_on(sysex: &[u8]) -> bool {
+	sysex.first() == Some(&0xF0) && sysex.get(1) == Some(&0x43) && is_reset(sysex)
+}
+
+// A voice as selected in either standard.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tone {
+	// Variation and program.
+	Gs { var: u8, prg: u8, rhythm: bool },
+	Xg(Selection),
+}
+
+impl fmt::Display for Tone {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Tone::Gs { prg, rhythm: true, .. } => {
+				let name = GSDrums.find(127, 0, prg).map(|v| v.name());
+				match (prg, name) {
+					(_, Some(name)) => write!(f, "GS kit {:03} {}", prg, name),
+					(GS_SFX_KIT, None) => write!(f, "GS kit {:03} SFX Kit", prg),
+					(_, None) => write!(f, "GS kit {:03}", prg),
+				}
+			}
+			Tone::Gs { var, prg, rhythm: false } => {
+				let name = match var {
+					0 => GM.find(0, 0, prg).map(|v| v.name()),
+					_ => EQUIVALENTS.iter()
+						.find(|e| e.gs_var == var && e.gs_prg == prg)
+						.map(|e| e.gs_name),
+				};
+				write!(f, "GS {:03}-{:03} {}", var, prg, name.unwrap_or("?"))
+			}
+			Tone::Xg(sel) => {
+				let name = xg_voice(sel.msb, sel.lsb, sel.prg).map_or("?", |v| v.name());
+				write!(f, "XG {} {}", sel, name)
+			}
+		}
+	}
+}
+
+// Returns the equivalent tone in the other standard, and whether it is an
+// exact equivalent rather than a fallback.
+pub fn convert_tone(tone: Tone) -> (Tone, bool) {
+	match tone {
+		Tone::Gs { prg, rhythm: true, .. } => {
+			let (msb, prg, exact) = match prg {
+				GS_SFX_KIT => (126, 1, true),
+				prg if GSDrums.find(127, 0, prg).is_some() => (127, prg, true),
+				_ => (127, 1, false),
+			};
+			(Tone::Xg(Selection { msb, lsb: 0, prg }), exact)
+		}
+		Tone::Gs { var: 0, prg, .. } => (Tone::Xg(Selection { msb: 0, lsb: 0, prg }), true),
+		Tone::Gs { var, prg, .. } => {
+			match EQUIVALENTS.iter().find(|e| e.gs_var == var && e.gs_prg == prg) {
+				Some(e) => {
+					let sel = Selection { msb: e.xg_msb, lsb: e.xg_lsb, prg: e.xg_prg };
+					(Tone::Xg(sel), true)
+				}
+				None => (Tone::Xg(Selection { msb: 0, lsb: 0, prg }), false),
+			}
+		}
+		Tone::Xg(sel) => {
+			let gs = |var, prg, rhythm| Tone::Gs { var, prg, rhythm };
+			if let Some(e) = EQUIVALENTS.iter()
+				.find(|e| (e.xg_msb, e.xg_lsb, e.xg_prg) == (sel.msb, sel.lsb, sel.prg))
+			{
+				return (gs(e.gs_var, e.gs_prg, false), true);
+			}
+			match VoiceKind::of_msb(sel.msb) {
+				VoiceKind::Kit if sel.msb == 126 => (gs(0, GS_SFX_KIT, true), true),
+				VoiceKind::Kit => match GSDrums.find(127, 0, sel.prg) {
+					Some(_) => (gs(0, sel.prg, true), sel.lsb == 0),
+					None => (gs(0, 1, true), false),
+				},
+				// XG's SFX bank is laid out in blocks of 16 programs that
+				// correspond to GM's 8 sound effect programs.
+				VoiceKind::Sfx => (gs(0, 121 + (sel.prg - 1) / 16, false), false),
+				VoiceKind::Instrument => (gs(0, sel.prg, false), sel.msb == 0 && sel.lsb == 0),
+			}
+		}
+	}
+}
+
+pub struct Conversion {
+	pub track: usize,
+	pub tick: u64,
+	pub channel: u8,
+	pub from: Tone,
+	pub to: Tone,
+	pub exact: bool,
+}
+
+impl fmt::Display for Conversion {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f,
+			"track {:>2}, tick {:>6}, channel {:>2}: {} -> {}{}",
+			self.track + 1, self.tick, self.channel + 1, self.from, self.to,
+			if self.exact { "" } else { " (fallback)" }
+		)
+	}
+}
+
+// Per-channel bank state on either side of the conversion.
+#[derive(Clone, Copy)]
+struct Part {
+	msb: u8,
+	lsb: u8,
+	rhythm: bool,
+}
+
+fn default_parts() -> [Part; 16] {
+	let mut ret = [Part { msb: 0, lsb: 0, rhythm: false }; 16];
+	ret[DRUM_CHANNEL as usize] = Part { msb: DRUM_MSB, lsb: 0, rhythm: true };
+	ret
+}
+
+struct Converter<'a> {
+	to: Standard,
+	input: [Part; 16],
+	output: [Part; 16],
+	out: &'a mut Vec<TrackEvent>,
+	tick: u64,
+}
+
+impl<'a> Converter<'a> {
+	fn push(&mut self, event: Event) {
+		self.out.push(TrackEvent { tick: self.tick, event });
+	}
+
+	fn control(&mut self, channel: u8, controller: u8, value: u8) {
+		let msg = ChannelMsg::Control { controller, value };
+		self.push(Event::Channel { channel, msg });
+	}
+
+	// Emits everything needed to select `tone` on the output side.
+	fn select(&mut self, channel: u8, tone: Tone) {
+		let part = self.output[channel as usize];
+		let prg = match tone {
+			Tone::Xg(sel) => {
+				if part.msb != sel.msb {
+					self.control(channel, CC_BANK_MSB, sel.msb);
+				}
+				if part.lsb != sel.lsb {
+					self.control(channel, CC_BANK_LSB, sel.lsb);
+				}
+				let rhythm = VoiceKind::of_msb(sel.msb) == VoiceKind::Kit;
+				self.output[channel as usize] = Part { msb: sel.msb, lsb: sel.lsb, rhythm };
+				sel.prg
+			}
+			Tone::Gs { var, prg, rhythm } => {
+				if part.rhythm != rhythm {
+					let map = match (rhythm, channel) {
+						(false, _) => 0,
+						(true, DRUM_CHANNEL) => 1,
+						(true, _) => 2,
+					};
+					self.push(Event::SysEx(gs_rhythm_sysex(channel, map)));
+				}
+				// CC0 is ignored on rhythm parts.
+				if !rhythm && part.msb != var {
+					self.control(channel, CC_BANK_MSB, var);
+				}
+				let msb = if rhythm { part.msb } else { var };
+				self.output[channel as usize] = Part { msb, lsb: 0, rhythm };
+				prg
+			}
+		};
+		let msg = ChannelMsg::Program(prg - 1);
+		self.push(Event::Channel { channel, msg });
+	}
+
+	// The tone that a Program Change would select on the input side.
+	fn input_tone(&self, channel: u8, prg: u8) -> Tone {
+		let part = self.input[channel as usize];
+		match self.to {
+			Standard::Xg => Tone::Gs { var: part.msb, prg, rhythm: part.rhythm },
+			Standard::Gs => Tone::Xg(Selection { msb: part.msb, lsb: part.lsb, prg }),
+		}
+	}
+
+	// Converts the selection of `prg` (1-based) on the input side.
+	fn program(&mut self, channel: u8, prg: u8, log: &mut Vec<Conversion>, track: usize) {
+		let from = self.input_tone(channel, prg);
+		let (to, exact) = convert_tone(from);
+		log.push(Conversion { track, tick: self.tick, channel, from, to, exact });
+		self.select(channel, to);
+	}
+
+	// Handles an input event, returning whether it was consumed.
+	fn convert(&mut self, event: &Event, log: &mut Vec<Conversion>, track: usize) -> bool {
+		match *event {
+			Event::Channel { channel, msg: ChannelMsg::Control { controller, value } } => {
+				let part = &mut self.input[channel as usize];
+				match controller {
+					CC_BANK_MSB => part.msb = value,
+					CC_BANK_LSB => part.lsb = value,
+					_ => return false,
+				}
+				true
+			}
+			Event::Channel { channel, msg: ChannelMsg::Program(prg) } => {
+				self.program(channel, prg + 1, log, track);
+				true
+			}
+			Event::SysEx(ref data) if is_gs_reset(data) && self.to == Standard::Xg => {
+				self.input = default_parts();
+				self.output = default_parts();
+				self.push(Event::SysEx(sysex::xg_system_on(0)));
+				true
+			}
+			Event::SysEx(ref data) if is_xg_system_on(data) && self.to == Standard::Gs => {
+				self.input = default_parts();
+				self.output = default_parts();
+				self.push(Event::SysEx(sysex::gs_reset(0)));
+				true
+			}
+			Event::SysEx(ref data) if is_reset(data) => {
+				self.input = default_parts();
+				self.output = default_parts();
+				false
+			}
+			Event::SysEx(ref data) => {
+				// Rhythm part assignments take effect immediately, so the
+				// output has to select a kit (or voice) right away.
+				let assignment = match self.to {
+					Standard::Xg => parse_gs_rhythm(data).map(|(ch, map)| (ch, map != 0)),
+					Standard::Gs => parse_xg_part_mode(data).map(|(ch, mode)| (ch, mode != 0)),
+				};
+				match assignment {
+					Some((channel, rhythm)) => {
+						let part = &mut self.input[channel as usize];
+						part.rhythm = rhythm;
+						part.msb = if rhythm { DRUM_MSB } else { 0 };
+						part.lsb = 0;
+						self.program(channel, 1, log, track);
+						true
+					}
+					None => false,
+				}
+			}
+			_ => false,
+		}
+	}
+}
+
+// Converts a file written for the other standard to `to`, returning the
+// converted file and a log of every converted voice selection.
+pub fn convert(smf: &Smf, to: Standard) -> (Smf, Vec<Conversion>) {
+	let mut tracks: Vec<Vec<TrackEvent>> = vec![Vec::new(); smf.tracks.len()];
+	let mut log = Vec::new();
+	for sequence in smf.sequences() {
+		let mut input = default_parts();
+		let mut output = default_parts();
+		for (track, e) in sequence {
+			let mut converter = Converter {
+				to, input, output, out: &mut tracks[track], tick: e.tick
+			};
+			if !converter.convert(&e.event, &mut log, track) {
+				converter.push(e.event.clone());
+			}
+			input = converter.input;
+			output = converter.output;
+		}
+	}
+	let smf = Smf {
+		format: smf.format,
+		division: smf.division,
+		tracks: tracks.into_iter().map(|events| Track { events }).collect(),
+	};
+	(smf, log)
+}
@@ -0,0 +1,262 @@
+/*
+ * Static HTML voice list generator, laid out like the voice lists in
+ * Yamaha's manuals. Writes a self-contained directory that can be opened
+ * straight from the file system.
+ */
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::json::Value;
+use super::models::Model;
+use super::voices::*;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 1em 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #aaa; padding: 0.2em 0.4em; vertical-align: top; }
+th { background: #eee; }
+td:target { background: #ffd; }
+.also { display: block; font-size: 75%; color: #666; }
+#results li { margin: 0.1em 0; }
+";
+
+const SEARCH_JS: &str = "\
+function search(query) {
+	var results = document.getElementById('results');
+	results.innerHTML = '';
+	query = query.trim().toLowerCase();
+	if(query.length === 0) {
+		return;
+	}
+	var count = 0;
+	for(var i = 0; i < VOICES.length && count < 200; i++) {
+		var v = VOICES[i];
+		if(v[3].toLowerCase().indexOf(query) === -1) {
+			continue;
+		}
+		var a = document.createElement('a');
+		a.href = v[1] + '#' + v[2];
+		a.textContent = v[0] + ': ' + v[2].substring(2) + ' ' + v[3];
+		var li = document.createElement('li');
+		li.appendChild(a);
+		results.appendChild(li);
+		count++;
+	}
+}
+";
+
+struct Escaped<'a>(&'a str);
+
+impl<'a> fmt::Display for Escaped<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for c in self.0.chars() {
+			match c {
+				'&' => f.write_str("&amp;")?,
+				'<' => f.write_str("&lt;")?,
+				'>' => f.write_str("&gt;")?,
+				'"' => f.write_str("&quot;")?,
+				c => f.write_char(c)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+// File name of the main page of a model.
+fn page(model: &Model) -> String {
+	let slug: String = model.name.chars()
+		.map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+		.collect();
+	format!("{}.html", slug)
+}
+
+fn kits_page(model: &Model) -> String {
+	page(model).replace(".html", "_kits.html")
+}
+
+// Page that lists the given voice on the given model.
+fn voice_page(model: &Model, voice: &Voice) -> String {
+	match voice.kind() {
+		VoiceKind::Kit => kits_page(model),
+		_ => page(model),
+	}
+}
+
+fn anchor(voice: &Voice) -> String {
+	format!("v-{:03}-{:03}-{:03}", voice.msb, voice.lsb, voice.prg)
+}
+
+fn header<W: Write>(out: &mut W, title: &str) -> io::Result<()> {
+	write!(out,
+"<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{0}</title>
+<link rel=\"stylesheet\" href=\"style.css\">
+<script src=\"index.js\"></script>
+<script src=\"search.js\"></script>
+</head>
+<body>
+<p><a href=\"index.html\">All models</a> |
+<input type=\"search\" placeholder=\"Search voices…\" oninput=\"search(this.value)\"></p>
+<ul id=\"results\"></ul>
+<h1>{0}</h1>
+", Escaped(title))
+}
+
+fn footer<W: Write>(out: &mut W) -> io::Result<()> {
+	out.write_all(b"</body>\n</html>\n")
+}
+
+// Name of the voice, followed by links to all other models that have a
+// voice at the same address.
+fn voice_cell<W: Write>(out: &mut W, model: &Model, voice: &Voice, models: &[&Model])
+	-> io::Result<()>
+{
+	write!(out, "<td id=\"{}\">{}", anchor(voice), Escaped(voice.name()))?;
+	let others: Vec<String> = models.iter()
+		.filter(|m| m.name != model.name)
+		.filter_map(|m| m.find(voice.msb, voice.lsb, voice.prg).map(|v| (m, v)))
+		.map(|(m, v)| {
+			let title = if v.name() != voice.name() {
+				format!(" title=\"{}\"", Escaped(v.name()))
+			} else {
+				String::new()
+			};
+			format!(
+				"<a href=\"{}#{}\"{}>{}</a>",
+				voice_page(m, v), anchor(v), title, Escaped(m.name)
+			)
+		})
+		.collect();
+	if !others.is_empty() {
+		write!(out, "<span class=\"also\">Also on {}</span>", others.join(", "))?;
+	}
+	out.write_all(b"</td>")
+}
+
+// Program number grid with one column per LSB, for all voices with the
+// given MSB.
+fn grid<W: Write>(out: &mut W, model: &Model, msb: u8, models: &[&Model])
+	-> io::Result<()>
+{
+	let voices: Vec<&Voice> = model.voices().filter(|v| v.msb == msb).collect();
+	let lsbs: BTreeSet<u8> = voices.iter().map(|v| v.lsb).collect();
+	let prgs: BTreeSet<u8> = voices.iter().map(|v| v.prg).collect();
+	writeln!(out, "<h2>MSB {:03}</h2>\n<table>", msb)?;
+	out.write_all(b"<tr><th>Program</th>")?;
+	for lsb in &lsbs {
+		write!(out, "<th>LSB {:03}</th>", lsb)?;
+	}
+	out.write_all(b"</tr>\n")?;
+	for &prg in &prgs {
+		write!(out, "<tr><th>{:03}</th>", prg)?;
+		for &lsb in &lsbs {
+			match voices.iter().find(|v| v.lsb == lsb && v.prg == prg) {
+				Some(voice) => voice_cell(out, model, voice, models)?,
+				None => out.write_all(b"<td></td>")?,
+			}
+		}
+		out.write_all(b"</tr>\n")?;
+	}
+	out.write_all(b"</table>\n")
+}
+
+fn model_page<W: Write>(out: &mut W, model: &Model, models: &[&Model]) -> io::Result<()> {
+	header(out, &model.title())?;
+	writeln!(out, "<p>{}</p>", VoiceCounts::by_kind(model.voices()))?;
+	if model.voices().any(|v| v.kind() == VoiceKind::Kit) {
+		writeln!(out, "<p><a href=\"{}\">Drum kits</a></p>", kits_page(model))?;
+	}
+	let msbs: BTreeSet<u8> = model.voices()
+		.filter(|v| v.kind() != VoiceKind::Kit)
+		.map(|v| v.msb)
+		.collect();
+	for msb in msbs {
+		grid(out, model, msb, models)?;
+	}
+	footer(out)
+}
+
+fn model_kits_page<W: Write>(out: &mut W, model: &Model, models: &[&Model]) -> io::Result<()> {
+	header(out, &format!("{} drum kits", model.name))?;
+	writeln!(out, "<p><a href=\"{}\">Voices</a></p>", page(model))?;
+	out.write_all(b"<table>\n<tr><th>MSB</th><th>LSB</th><th>Program</th><th>Kit</th></tr>\n")?;
+	for voice in model.voices().filter(|v| v.kind() == VoiceKind::Kit) {
+		write!(out,
+			"<tr><td>{:03}</td><td>{:03}</td><td>{:03}</td>", voice.msb, voice.lsb, voice.prg
+		)?;
+		voice_cell(out, model, voice, models)?;
+		out.write_all(b"</tr>\n")?;
+	}
+	out.write_all(b"</table>\n")?;
+	footer(out)
+}
+
+fn index_page<W: Write>(out: &mut W, models: &[&Model]) -> io::Result<()> {
+	header(out, "Yamaha voice lists")?;
+	out.write_all(b"<table>\n<tr><th>Model</th><th>Year</th><th>Voices</th></tr>\n")?;
+	for model in models {
+		writeln!(out,
+			"<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>",
+			page(model),
+			Escaped(model.name),
+			model.year.map_or(String::new(), |y| y.to_string()),
+			VoiceCounts::by_kind(model.voices())
+		)?;
+	}
+	out.write_all(b"</table>\n")?;
+	footer(out)
+}
+
+// Search index, as a script rather than JSON so that it can be loaded from
+// `file://` URLs. Every entry is [model, page, anchor, name, kind].
+fn index_js(models: &[&Model]) -> String {
+	let string = |s: &str| Value::String(s.to_string());
+	let mut entries = Vec::new();
+	for model in models {
+		for voice in model.voices() {
+			entries.push(Value::Array(vec![
+				string(model.name),
+				string(&voice_page(model, voice)),
+				string(&anchor(voice)),
+				string(voice.name()),
+				string(voice.kind().id()),
+			]));
+		}
+	}
+	format!("var VOICES = {};\n", Value::Array(entries))
+}
+
+// Writes a page through a buffer, flushing it so that errors like a full
+// disk aren't lost when the buffer is dropped.
+fn write_page<F>(dir: &Path, name: &str, page: F) -> io::Result<()>
+where
+	F: FnOnce(&mut BufWriter<fs::File>) -> io::Result<()>,
+{
+	let mut out = BufWriter::new(fs::File::create(dir.join(name))?);
+	page(&mut out)?;
+	out.flush()
+}
+
+pub fn generate(dir: &Path, models: &[&Model]) -> io::Result<()> {
+	fs::create_dir_all(dir)?;
+	fs::write(dir.join("style.css"), STYLE)?;
+	fs::write(dir.join("search.js"), SEARCH_JS)?;
+	fs::write(dir.join("index.js"), index_js(models))?;
+	write_page(dir, "index.html", |out| index_page(out, models))?;
+	for model in models {
+		write_page(dir, &page(model), |out| model_page(out, model, models))?;
+		if model.voices().any(|v| v.kind() == VoiceKind::Kit) {
+			write_page(dir, &kits_page(model), |out| model_kits_page(out, model, models))?;
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,129 @@
+/*
+ * Detects the model of a Yamaha module from its Identity Reply, or from the
+ * model ID of a bulk dump it sent.
+ *
+ * Identity Reply: F0 7E <device> 06 02 <manufacturer> <family, 2 bytes>
+ * <member, 2 bytes> <version, 4 bytes> F7. Family and member codes are
+ * compared as the byte pairs of the reply.
+ *
+ * A bulk dump only tells whether it is in XG or MU native format, which many
+ * modules share, so it doesn't name a model.
+ */
+
+use std::fmt;
+
+use super::models::{self, Model};
+use super::sysex::Hex;
+
+const YAMAHA: u8 = 0x43;
+
+// Model IDs of Yamaha System Exclusive messages.
+const XG_MODEL: u8 = 0x4C;
+const MU_NATIVE_MODEL: u8 = 0x49;
+
+pub struct Identity {
+	pub manufacturer: u8,
+	pub family: [u8; 2],
+	pub member: [u8; 2],
+	pub version: [u8; 4],
+}
+
+impl Identity {
+	// Parses an Identity Reply, including the leading F0 and trailing F7.
+	pub fn parse(sysex: &[u8]) -> Option<Identity> {
+		match *sysex {
+			[0xF0, 0x7E, _, 0x06, 0x02, manufacturer, f0, f1, m0, m1, v0, v1, v2, v3, 0xF7] => {
+				Some(Identity {
+					manufacturer,
+					family: [f0, f1],
+					member: [m0, m1],
+					version: [v0, v1, v2, v3],
+				})
+			}
+			_ => None,
+		}
+	}
+}
+
+// Family and member codes of the built-in models that answer Identity
+// Requests. The MU5 predates them.
+const DEVICES: &[([u8; 2], [u8; 2], &str)] = &[
+	([0x00, 0x41], [0x34, 0x01], "MU80"),
+];
+
+// The Identity Reply that `model` sends as `device`, if it answers Identity
+// Requests.
+pub fn reply(device: u8, model: &Model) -> Option<Vec<u8>> {
+	let &(family, member, _) = DEVICES.iter().find(|d| d.2 == model.name)?;
+	let mut ret = vec![0xF0, 0x7E, device, 0x06, 0x02, YAMAHA];
+	ret.extend_from_slice(&family);
+	ret.extend_from_slice(&member);
+	ret.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0xF7]);
+	Some(ret)
+}
+
+pub struct Detection {
+	// None if the message only names a kind of module.
+	pub model: Option<&'static Model>,
+	// The model, or the kind of module, as in "XG module".
+	pub name: &'static str,
+	pub source: String,
+}
+
+impl fmt::Display for Detection {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} (from {})", self.name, self.source)
+	}
+}
+
+// Detects the model that sent `sysex`, an Identity Reply or a Yamaha bulk
+// dump or parameter change.
+pub fn detect(sysex: &[u8]) -> Result<Detection, String> {
+	if let Some(id) = Identity::parse(sysex) {
+		if id.manufacturer != YAMAHA {
+			return Err(format!("Identity Reply from manufacturer {:02X}", id.manufacturer));
+		}
+		let source = format!("Identity Reply, version {}", Hex(&id.version));
+		return match DEVICES.iter().find(|d| d.0 == id.family && d.1 == id.member) {
+			Some(&(_, _, name)) => {
+				let model = models::find(name).ok_or_else(|| format!("no built-in {}", name))?;
+				Ok(Detection { model: Some(model), name: model.name, source })
+			}
+			None => Err(format!(
+				"unknown Yamaha family {}, member {}", Hex(&id.family), Hex(&id.member)
+			)),
+		};
+	}
+	let (name, source) = match *sysex {
+		[0xF0, YAMAHA, _, XG_MODEL, ..] => ("XG module", "XG model ID"),
+		[0xF0, YAMAHA, _, MU_NATIVE_MODEL, ..] => ("MU module", "MU native model ID"),
+		[0xF0, YAMAHA, _, model, ..] => {
+			return Err(format!("unknown Yamaha model ID {:02X}", model));
+		}
+		_ => return Err("neither an Identity Reply nor a Yamaha message".to_string()),
+	};
+	Ok(Detection { model: None, name, source: source.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn reply_round_trips() {
+		let mu80 = models::find("MU80").unwrap();
+		let bytes = reply(0x10, mu80).unwrap();
+		assert_eq!(bytes[6..10], [0x00, 0x41, 0x34, 0x01]);
+		let detection = detect(&bytes).unwrap();
+		assert_eq!(detection.model.map(|m| m.name), Some("MU80"));
+		assert!(reply(0x10, models::find("MU5").unwrap()).is_none());
+	}
+
+	#[test]
+	fn dump_header_names_no_model() {
+		let detection = detect(&[0xF0, 0x43, 0x00, 0x4C, 0x00, 0x00, 0x7E, 0x00, 0xF7]).unwrap();
+		assert!(detection.model.is_none());
+		assert_eq!(detection.to_string(), "XG module (from XG model ID)");
+		assert!(detect(&[0xF0, 0x43, 0x10, 0x4B, 0xF7]).is_err());
+	}
+}
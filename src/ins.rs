@@ -0,0 +1,440 @@
+/*
+ * Cakewalk instrument definition (`.ins`) import.
+ *
+ * Only the parts relevant to voice selection are parsed: `.Patch Names`
+ * sections (including `BasedOn=` inheritance) and the `Patch[]`, `Drum[]`
+ * and `BankSelMethod=` entries of `.Instrument Definitions`.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::models::{self, Model};
+use super::voices::*;
+
+#[derive(Debug)]
+pub enum Error {
+	Io(io::Error),
+	Syntax { line: usize, text: String },
+	MissingSection { group: &'static str, name: String },
+	InheritanceCycle(String),
+	// A `Patch[]` index that `BankSelMethod` can't send.
+	BankOutOfRange { instrument: String, number: u16, method: u8 },
+	// Two `Patch[]` indices that select the same bank.
+	BankCollision { instrument: String, numbers: (u16, u16), msb: u8, lsb: u8 },
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Io(ref e) => write!(f, "{}", e),
+			Error::Syntax { line, ref text } => {
+				write!(f, "line {}: can't parse \"{}\"", line, text)
+			}
+			Error::MissingSection { group, ref name } => {
+				write!(f, "[{}] is not defined in .{}", name, group)
+			}
+			Error::InheritanceCycle(ref name) => {
+				write!(f, "[{}] is based on itself", name)
+			}
+			Error::BankOutOfRange { ref instrument, number, method } => write!(f,
+				"[{}]: Patch[{}] is out of range for BankSelMethod={}", instrument, number, method
+			),
+			Error::BankCollision { ref instrument, numbers, msb, lsb } => write!(f,
+				"[{}]: Patch[{}] and Patch[{}] both select bank {:03}-{:03}",
+				instrument, numbers.0, numbers.1, msb, lsb
+			),
+		}
+	}
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Error {
+		Error::Io(e)
+	}
+}
+
+const PATCH_NAMES: &str = "Patch Names";
+const INSTRUMENT_DEFINITIONS: &str = "Instrument Definitions";
+
+// Key/value pairs of a single [section], in file order.
+type Section = Vec<(String, String)>;
+
+// All sections of a single .group.
+type Group = Vec<(String, Section)>;
+
+pub struct Bank {
+	// Index of the `Patch[]` entry. Its meaning depends on `BankSelMethod`.
+	pub number: u16,
+	pub msb: u8,
+	pub lsb: u8,
+	// 0-based program numbers, as used in .ins files.
+	pub patches: BTreeMap<u8, String>,
+}
+
+// `Drum[bank,program]=1`. `None` stands for the `*` wildcard.
+struct DrumFlag {
+	bank: Option<u16>,
+	prg: Option<u8>,
+	drum: bool,
+}
+
+pub struct Instrument {
+	pub name: String,
+	pub banks: Vec<Bank>,
+	drums: Vec<DrumFlag>,
+}
+
+pub struct InsFile {
+	pub instruments: Vec<Instrument>,
+}
+
+fn parse_groups(text: &str) -> Result<HashMap<String, Group>, Error> {
+	let mut groups: HashMap<String, Group> = HashMap::new();
+	let mut group: Option<String> = None;
+	for (i, line) in text.lines().enumerate() {
+		let line = line.trim_start_matches('\u{feff}').trim();
+		if line.is_empty() || line.starts_with(';') {
+			continue;
+		}
+		let syntax_error = || Error::Syntax { line: i + 1, text: line.to_string() };
+		if let Some(name) = line.strip_prefix('.') {
+			groups.entry(name.trim().to_string()).or_default();
+			group = Some(name.trim().to_string());
+		} else if let Some(name) = line.strip_prefix('[') {
+			let name = name.strip_suffix(']').ok_or_else(syntax_error)?;
+			let group = group.as_ref().ok_or_else(syntax_error)?;
+			let sections = groups.get_mut(group).unwrap();
+			sections.push((name.to_string(), Vec::new()));
+		} else {
+			let (key, value) = line.split_once('=').ok_or_else(syntax_error)?;
+			let section = group.as_ref()
+				.and_then(|g| groups.get_mut(g).unwrap().last_mut())
+				.ok_or_else(syntax_error)?;
+			section.1.push((key.trim().to_string(), value.trim().to_string()));
+		}
+	}
+	Ok(groups)
+}
+
+fn find_section<'a>(group: Option<&'a Group>, group_name: &'static str, name: &str)
+	-> Result<&'a Section, Error>
+{
+	group
+		.and_then(|g| g.iter().find(|s| s.0 == name))
+		.map(|s| &s.1)
+		.ok_or_else(|| Error::MissingSection { group: group_name, name: name.to_string() })
+}
+
+// Resolves `BasedOn=` chains, with the entries of derived sections taking
+// precedence over those of their bases.
+fn resolve_patch_names(group: Option<&Group>, name: &str, chain: &mut Vec<String>)
+	-> Result<BTreeMap<u8, String>, Error>
+{
+	if chain.iter().any(|c| c == name) {
+		return Err(Error::InheritanceCycle(name.to_string()));
+	}
+	chain.push(name.to_string());
+	let section = find_section(group, PATCH_NAMES, name)?;
+	let mut ret = BTreeMap::new();
+	if let Some(base) = section.iter().find(|kv| kv.0 == "BasedOn") {
+		ret = resolve_patch_names(group, &base.1, chain)?;
+	}
+	for (key, value) in section {
+		if let Ok(prg) = key.parse::<u8>() {
+			if prg < 128 {
+				ret.insert(prg, value.clone());
+			}
+		}
+	}
+	chain.pop();
+	Ok(ret)
+}
+
+// Returns the index within the brackets of `Patch[…]` or `Drum[…]`.
+fn bracket_arg<'a>(key: &'a str, name: &str) -> Option<&'a str> {
+	key.strip_prefix(name)?.trim().strip_prefix('[')?.strip_suffix(']')
+}
+
+fn wildcard<T: std::str::FromStr>(s: &str) -> Option<Option<T>> {
+	match s.trim() {
+		"*" => Some(None),
+		s => s.parse().ok().map(Some),
+	}
+}
+
+// Splits a `Patch[]` index into MSB and LSB, according to the
+// `BankSelMethod` of the instrument, or None if the method can't send it.
+fn bank_select(method: u8, number: u16) -> Option<(u8, u8)> {
+	match method {
+		1 if number < 0x80 => Some((number as u8, 0)),
+		2 if number < 0x80 => Some((0, number as u8)),
+		3 => Some((0, 0)),
+		0 | 4.. if number < 0x4000 => Some(((number >> 7) as u8, (number & 0x7F) as u8)),
+		_ => None,
+	}
+}
+
+fn parse_instrument(
+	groups: &HashMap<String, Group>, name: &str, section: &Section
+) -> Result<Instrument, Error> {
+	let patch_names = groups.get(PATCH_NAMES);
+	let method = section.iter()
+		.find(|kv| kv.0 == "BankSelMethod")
+		.and_then(|kv| kv.1.parse().ok())
+		.unwrap_or(0);
+	let mut banks: Vec<Bank> = Vec::new();
+	let mut fallback = None;
+	let mut drums = Vec::new();
+	for (key, value) in section {
+		if let Some(arg) = bracket_arg(key, "Patch") {
+			let patches = resolve_patch_names(patch_names, value, &mut Vec::new())?;
+			match wildcard::<u16>(arg) {
+				Some(Some(number)) => {
+					let instrument = name.to_string();
+					let (msb, lsb) = bank_select(method, number)
+						.ok_or(Error::BankOutOfRange { instrument, number, method })?;
+					banks.retain(|b| b.number != number);
+					// With Program Change only, or numbers above 127 for a
+					// single bank select byte, different banks would become one.
+					if let Some(b) = banks.iter().find(|b| (b.msb, b.lsb) == (msb, lsb)) {
+						let instrument = name.to_string();
+						let numbers = (b.number, number);
+						return Err(Error::BankCollision { instrument, numbers, msb, lsb });
+					}
+					banks.push(Bank { number, msb, lsb, patches });
+				}
+				// Applies to all banks without their own entry, but we can
+				// only meaningfully place it at bank 0.
+				Some(None) => fallback = Some(patches),
+				None => {}
+			}
+		} else if let Some(arg) = bracket_arg(key, "Drum") {
+			let mut it = arg.splitn(2, ',');
+			let bank = it.next().and_then(wildcard);
+			let prg = it.next().and_then(wildcard);
+			if let (Some(bank), Some(prg)) = (bank, prg) {
+				drums.push(DrumFlag { bank, prg, drum: value.trim() == "1" });
+			}
+		}
+	}
+	if let Some(patches) = fallback {
+		if !banks.iter().any(|b| b.msb == 0 && b.lsb == 0) {
+			banks.push(Bank { number: 0, msb: 0, lsb: 0, patches });
+		}
+	}
+	banks.sort_by_key(|b| (b.msb, b.lsb));
+	Ok(Instrument { name: name.to_string(), banks, drums })
+}
+
+impl InsFile {
+	pub fn parse(text: &str) -> Result<InsFile, Error> {
+		let groups = parse_groups(text)?;
+		let mut instruments = Vec::new();
+		if let Some(defs) = groups.get(INSTRUMENT_DEFINITIONS) {
+			for (name, section) in defs {
+				instruments.push(parse_instrument(&groups, name, section)?);
+			}
+		}
+		Ok(InsFile { instruments })
+	}
+
+	// .ins files are typically encoded in some Windows code page, so invalid
+	// UTF-8 is replaced rather than rejected.
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<InsFile, Error> {
+		let bytes = fs::read(path)?;
+		InsFile::parse(&String::from_utf8_lossy(&bytes))
+	}
+
+	pub fn instrument(&self, name: &str) -> Option<&Instrument> {
+		self.instruments.iter().find(|i| i.name.eq_ignore_ascii_case(name))
+	}
+}
+
+impl Instrument {
+	// Evaluates the `Drum[]` entries for the given bank and 0-based program.
+	// Later entries override earlier ones, just like in Cakewalk.
+	pub fn is_drum(&self, bank: u16, prg: u8) -> bool {
+		self.drums.iter()
+			.rev()
+			.find(|d| d.bank.is_none_or(|b| b == bank) && d.prg.is_none_or(|p| p == prg))
+			.is_some_and(|d| d.drum)
+	}
+
+	pub fn to_model(&self) -> &'static Model {
+		let voicesets = self.banks.iter().map(|bank| {
+			Voices::leak(bank.patches.iter().map(|(&prg, name)| Voice {
+				msb: bank.msb,
+				lsb: bank.lsb,
+				prg: prg + 1,
+				name: Box::leak(name.clone().into_boxed_str()),
+			}).collect())
+		}).collect::<Vec<_>>();
+		let xg_level = models::guess_xg_level(&voicesets);
+		Model::leak(self.name.clone(), None, voicesets, Vec::new(), xg_level)
+	}
+}
+
+pub enum Discrepancy {
+	Name { builtin: &'static Voice, imported: &'static Voice },
+	// Same name, but at a different address.
+	Address { builtin: &'static Voice, imported: &'static Voice },
+	Missing(&'static Voice),
+	Extra(&'static Voice),
+	Drum { imported: &'static Voice, flagged: bool },
+}
+
+impl Discrepancy {
+	fn voice(&self) -> &'static Voice {
+		match *self {
+			Discrepancy::Name { builtin, .. } => builtin,
+			Discrepancy::Address { builtin, .. } => builtin,
+			Discrepancy::Missing(v) => v,
+			Discrepancy::Extra(v) => v,
+			Discrepancy::Drum { imported, .. } => imported,
+		}
+	}
+}
+
+impl fmt::Display for Discrepancy {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Discrepancy::Name { builtin, imported } => write!(f,
+				"~ {} (imported as \"{}\")", builtin, imported.name()
+			),
+			Discrepancy::Address { builtin, imported } => write!(f,
+				"> {} (imported at {:03}-{:03}-{:03})",
+				builtin, imported.msb, imported.lsb, imported.prg
+			),
+			Discrepancy::Missing(v) => write!(f, "- {}", v),
+			Discrepancy::Extra(v) => write!(f, "+ {}", v),
+			Discrepancy::Drum { imported, flagged: true } => write!(f,
+				"! {} (flagged as drums, but not a kit)", imported
+			),
+			Discrepancy::Drum { imported, flagged: false } => write!(f,
+				"! {} (is a kit, but not flagged as drums)", imported
+			),
+		}
+	}
+}
+
+// Compares an imported instrument against a built-in model.
+pub fn diff(instrument: &Instrument, builtin: &Model) -> Vec<Discrepancy> {
+	let imported = instrument.to_model();
+	let mut ret = Vec::new();
+	let mut missing: Vec<&'static Voice> = Vec::new();
+	let mut extra: Vec<&'static Voice> = Vec::new();
+
+	for b in builtin.voices() {
+		match imported.find(b.msb, b.lsb, b.prg) {
+			Some(i) if i.name() != b.name() => {
+				ret.push(Discrepancy::Name { builtin: b, imported: i })
+			}
+			Some(_) => {}
+			None => missing.push(b),
+		}
+	}
+	for bank in &instrument.banks {
+		for &prg in bank.patches.keys() {
+			let i = imported.find(bank.msb, bank.lsb, prg + 1).unwrap();
+			if builtin.find(i.msb, i.lsb, i.prg).is_none() {
+				extra.push(i);
+			}
+			let flagged = instrument.is_drum(bank.number, prg);
+			if flagged != (i.kind() == VoiceKind::Kit) {
+				ret.push(Discrepancy::Drum { imported: i, flagged });
+			}
+		}
+	}
+	for b in missing {
+		let moved = extra.iter().position(|i| i.name().eq_ignore_ascii_case(b.name()));
+		match moved {
+			Some(pos) => ret.push(Discrepancy::Address { builtin: b, imported: extra.remove(pos) }),
+			None => ret.push(Discrepancy::Missing(b)),
+		}
+	}
+	ret.extend(extra.into_iter().map(Discrepancy::Extra));
+	ret.sort_by_key(|d| {
+		let v = d.voice();
+		(v.msb, v.lsb, v.prg)
+	});
+	ret
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::models;
+
+	const PATCH_NAMES: &str = "\
+.Patch Names
+[GM]
+0=Grand Piano
+1=Bright Piano
+[Variations]
+BasedOn=GM
+1=Bright Piano 2
+[Kits]
+0=Standard Kit
+";
+
+	fn parse(definition: &str) -> Result<InsFile, Error> {
+		InsFile::parse(&format!("{}.Instrument Definitions\n{}", PATCH_NAMES, definition))
+	}
+
+	#[test]
+	fn banks_and_drums() {
+		let file = parse("\
+[Test]
+Patch[0]=GM
+Patch[1]=Variations
+Patch[16256]=Kits
+Drum[16256,*]=1
+").unwrap();
+		let test = file.instrument("test").unwrap();
+		let banks: Vec<_> = test.banks.iter()
+			.map(|b| (b.number, b.msb, b.lsb, b.patches.values().cloned().collect::<Vec<_>>()))
+			.collect();
+		assert_eq!(banks, [
+			(0, 0, 0, vec!["Grand Piano".to_string(), "Bright Piano".to_string()]),
+			(1, 0, 1, vec!["Grand Piano".to_string(), "Bright Piano 2".to_string()]),
+			(16256, 127, 0, vec!["Standard Kit".to_string()]),
+		]);
+		assert!(test.is_drum(16256, 0));
+		assert!(!test.is_drum(0, 0));
+	}
+
+	#[test]
+	fn colliding_banks() {
+		let err = parse("[Test]\nBankSelMethod=1\nPatch[72]=GM\nPatch[200]=Variations\n");
+		assert!(matches!(err, Err(Error::BankOutOfRange { number: 200, method: 1, .. })));
+		let err = parse("[Test]\nBankSelMethod=3\nPatch[0]=GM\nPatch[1]=Variations\n");
+		assert!(matches!(err, Err(Error::BankCollision { numbers: (0, 1), msb: 0, lsb: 0, .. })));
+	}
+
+	#[test]
+	fn diff_against_built_in() {
+		let file = parse("\
+[Test]
+Patch[0]=GM
+Patch[1]=Variations
+Patch[16256]=Kits
+").unwrap();
+		let mu5 = models::find("MU5").unwrap();
+		let found: Vec<String> = diff(&file.instruments[0], mu5).iter()
+			.filter(|d| !matches!(**d, Discrepancy::Missing(_)))
+			.map(|d| d.to_string())
+			.collect();
+		assert_eq!(found, [
+			"+ 000-001-001 Grand Piano",
+			"+ 000-001-002 Bright Piano 2",
+			"! 127-000-001 Standard Kit (is a kit, but not flagged as drums)",
+		]);
+	}
+}
@@ -0,0 +1,607 @@
+/*
+ * JSON export and import of the voice database.
+ *
+ * Schema, version 1:
+ *
+ * {
+ *   "schema": "yamaha_db",
+ *   "version": 1,
+ *   "models": [{
+ *     "name": string,
+ *     "year": number | null,
+ *     "xg_level": "XG Level 1" | "MU100" | null,  // optional on import
+ *     "counts": counts,
+ *     "voicesets": [{
+ *       "counts": counts,
+ *       "voices": [{
+ *         "msb": number,      // 0-based, 0-127
+ *         "lsb": number,      // 0-based, 0-127
+ *         "program": number,  // 1-based, 1-128
+ *         "kind": "instrument" | "sfx" | "kit",
+ *         "name": string
+ *       }, …]
+ *     }, …],
+ *     "effectsets": [{
+ *       "counts": effect_counts,
+ *       "types": [{
+ *         "block": "reverb" | "chorus" | "variation" | "insertion",
+ *         "msb": number,      // 0-127
+ *         "lsb": number,      // 0-127
+ *         "name": string,
+ *         "parameters": [{
+ *           "number": number,      // 1-16
+ *           "name": string,
+ *           "min": number,         // raw values
+ *           "max": number,
+ *           "min_display": string, // as shown on the module, as in "0.3 s"
+ *           "max_display": string
+ *         }, …]
+ *       }, …]
+ *     }, …]
+ *   }, …]
+ * }
+ *
+ * counts = {
+ *   "instruments": number, "sfx": number, "kits": number, "total": number
+ * }
+ *
+ * effect_counts = {
+ *   "reverb": number, "chorus": number, "variation": number,
+ *   "insertion": number, "total": number
+ * }
+ *
+ * `kind` and `counts` are derived from the voice addresses, and are only
+ * exported for the convenience of other tools. The importer ignores them.
+ * Voice sets shared between models are repeated for every model.
+ * `effectsets` was added later, and is optional on import. `parameters` is
+ * derived from the type MSB, and also ignored by the importer.
+ *
+ * Future versions will only add keys; a change to the meaning of existing
+ * keys will increment `version`.
+ */
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::effectparams::{self, EffectParam};
+use super::effects::*;
+use super::models::{self, Model};
+use super::voices::*;
+
+pub const SCHEMA: &str = "yamaha_db";
+pub const VERSION: u32 = 1;
+
+pub enum Value {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Value>),
+	Object(Vec<(String, Value)>),
+}
+
+#[derive(Debug)]
+pub enum Error {
+	Io(io::Error),
+	Syntax { offset: usize, expected: &'static str },
+	Schema(String),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Io(ref e) => write!(f, "{}", e),
+			Error::Syntax { offset, expected } => {
+				write!(f, "JSON syntax error at byte {}: expected {}", offset, expected)
+			}
+			Error::Schema(ref msg) => write!(f, "invalid database: {}", msg),
+		}
+	}
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Error {
+		Error::Io(e)
+	}
+}
+
+// Writing
+// -------
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+	write!(f, "\"")?;
+	for c in s.chars() {
+		match c {
+			'"' => write!(f, "\\\"")?,
+			'\\' => write!(f, "\\\\")?,
+			'\n' => write!(f, "\\n")?,
+			'\r' => write!(f, "\\r")?,
+			'\t' => write!(f, "\\t")?,
+			c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+			c => write!(f, "{}", c)?,
+		}
+	}
+	write!(f, "\"")
+}
+
+impl Value {
+	fn is_container(&self) -> bool {
+		match *self {
+			Value::Array(ref a) => !a.is_empty(),
+			Value::Object(ref o) => !o.is_empty(),
+			_ => false,
+		}
+	}
+
+	// Containers that only hold scalars are written on a single line, which
+	// puts every voice on its own line.
+	fn write(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+		let nested = match *self {
+			Value::Array(ref a) => a.iter().any(Value::is_container),
+			Value::Object(ref o) => o.iter().any(|kv| kv.1.is_container()),
+			_ => false,
+		};
+		let (sep, pad, end) = if nested {
+			(",\n", "\t".repeat(indent + 1), format!("\n{}", "\t".repeat(indent)))
+		} else {
+			(", ", String::new(), String::new())
+		};
+		match *self {
+			Value::Null => write!(f, "null"),
+			Value::Bool(b) => write!(f, "{}", b),
+			Value::Number(n) => write!(f, "{}", n),
+			Value::String(ref s) => write_string(f, s),
+			Value::Array(ref a) => {
+				write!(f, "[{}", if nested { "\n" } else { "" })?;
+				for (i, v) in a.iter().enumerate() {
+					write!(f, "{}{}", if i > 0 { sep } else { "" }, pad)?;
+					v.write(f, indent + 1)?;
+				}
+				write!(f, "{}]", end)
+			}
+			Value::Object(ref o) => {
+				write!(f, "{{{}", if nested { "\n" } else { "" })?;
+				for (i, (k, v)) in o.iter().enumerate() {
+					write!(f, "{}{}", if i > 0 { sep } else { "" }, pad)?;
+					write_string(f, k)?;
+					write!(f, ": ")?;
+					v.write(f, indent + 1)?;
+				}
+				write!(f, "{}}}", end)
+			}
+		}
+	}
+}
+
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		self.write(f, 0)
+	}
+}
+
+// Parsing
+// -------
+
+struct Parser<'a> {
+	text: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn error(&self, expected: &'static str) -> Error {
+		Error::Syntax { offset: self.pos, expected }
+	}
+
+	fn skip_ws(&mut self) {
+		while self.pos < self.text.len() && self.text[self.pos].is_ascii_whitespace() {
+			self.pos += 1;
+		}
+	}
+
+	fn peek(&mut self) -> Option<u8> {
+		self.skip_ws();
+		self.text.get(self.pos).cloned()
+	}
+
+	fn eat(&mut self, c: u8, expected: &'static str) -> Result<(), Error> {
+		if self.peek() != Some(c) {
+			return Err(self.error(expected));
+		}
+		self.pos += 1;
+		Ok(())
+	}
+
+	fn literal(&mut self, lit: &str, value: Value) -> Result<Value, Error> {
+		if !self.text[self.pos..].starts_with(lit.as_bytes()) {
+			return Err(self.error("a value"));
+		}
+		self.pos += lit.len();
+		Ok(value)
+	}
+
+	fn hex4(&mut self) -> Result<u32, Error> {
+		let digits = self.text.get(self.pos..self.pos + 4)
+			.and_then(|d| std::str::from_utf8(d).ok())
+			.and_then(|d| u32::from_str_radix(d, 16).ok())
+			.ok_or_else(|| self.error("4 hex digits"))?;
+		self.pos += 4;
+		Ok(digits)
+	}
+
+	fn string(&mut self) -> Result<String, Error> {
+		self.eat(b'"', "a string")?;
+		let mut ret = Vec::new();
+		loop {
+			let c = *self.text.get(self.pos).ok_or_else(|| self.error("'\"'"))?;
+			self.pos += 1;
+			match c {
+				b'"' => break,
+				b'\\' => {
+					let e = *self.text.get(self.pos).ok_or_else(|| self.error("an escape"))?;
+					self.pos += 1;
+					let c = match e {
+						b'"' => '"',
+						b'\\' => '\\',
+						b'/' => '/',
+						b'b' => '\u{8}',
+						b'f' => '\u{c}',
+						b'n' => '\n',
+						b'r' => '\r',
+						b't' => '\t',
+						b'u' => {
+							let mut cp = self.hex4()?;
+							// UTF-16 surrogate pair
+							if (0xD800..0xDC00).contains(&cp) {
+								if !self.text[self.pos..].starts_with(b"\\u") {
+									return Err(self.error("a low surrogate"));
+								}
+								self.pos += 2;
+								let lo = self.hex4()?;
+								if !(0xDC00..0xE000).contains(&lo) {
+									return Err(self.error("a low surrogate"));
+								}
+								cp = 0x10000 + ((cp - 0xD800) << 10) + (lo - 0xDC00);
+							}
+							std::char::from_u32(cp).unwrap_or('\u{fffd}')
+						}
+						_ => return Err(self.error("an escape")),
+					};
+					let mut buf = [0; 4];
+					ret.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+				}
+				c => ret.push(c),
+			}
+		}
+		String::from_utf8(ret).map_err(|_| self.error("UTF-8"))
+	}
+
+	fn number(&mut self) -> Result<Value, Error> {
+		let start = self.pos;
+		while self.pos < self.text.len()
+			&& b"+-.eE0123456789".contains(&self.text[self.pos])
+		{
+			self.pos += 1;
+		}
+		std::str::from_utf8(&self.text[start..self.pos]).ok()
+			.and_then(|s| s.parse().ok())
+			.map(Value::Number)
+			.ok_or(Error::Syntax { offset: start, expected: "a number" })
+	}
+
+	fn value(&mut self) -> Result<Value, Error> {
+		match self.peek() {
+			Some(b'n') => self.literal("null", Value::Null),
+			Some(b't') => self.literal("true", Value::Bool(true)),
+			Some(b'f') => self.literal("false", Value::Bool(false)),
+			Some(b'"') => self.string().map(Value::String),
+			Some(b'[') => {
+				self.pos += 1;
+				let mut ret = Vec::new();
+				if self.peek() == Some(b']') {
+					self.pos += 1;
+					return Ok(Value::Array(ret));
+				}
+				loop {
+					ret.push(self.value()?);
+					match self.peek() {
+						Some(b',') => self.pos += 1,
+						_ => break,
+					}
+				}
+				self.eat(b']', "',' or ']'")?;
+				Ok(Value::Array(ret))
+			}
+			Some(b'{') => {
+				self.pos += 1;
+				let mut ret = Vec::new();
+				if self.peek() == Some(b'}') {
+					self.pos += 1;
+					return Ok(Value::Object(ret));
+				}
+				loop {
+					let key = self.string()?;
+					self.eat(b':', "':'")?;
+					ret.push((key, self.value()?));
+					match self.peek() {
+						Some(b',') => self.pos += 1,
+						_ => break,
+					}
+				}
+				self.eat(b'}', "',' or '}'")?;
+				Ok(Value::Object(ret))
+			}
+			Some(_) => self.number(),
+			None => Err(self.error("a value")),
+		}
+	}
+}
+
+impl Value {
+	pub fn parse(text: &str) -> Result<Value, Error> {
+		let mut parser = Parser { text: text.as_bytes(), pos: 0 };
+		let ret = parser.value()?;
+		if parser.peek().is_some() {
+			return Err(parser.error("end of input"));
+		}
+		Ok(ret)
+	}
+
+	pub fn get(&self, key: &str) -> Option<&Value> {
+		match *self {
+			Value::Object(ref o) => o.iter().find(|kv| kv.0 == key).map(|kv| &kv.1),
+			_ => None,
+		}
+	}
+}
+
+// Database schema
+// ---------------
+
+pub fn number<T: Into<f64>>(n: T) -> Value {
+	Value::Number(n.into())
+}
+
+pub fn object(pairs: Vec<(&str, Value)>) -> Value {
+	Value::Object(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn counts_value(counts: &VoiceCounts) -> Value {
+	object(vec![
+		("instruments", number(counts.instruments)),
+		("sfx", number(counts.sfx)),
+		("kits", number(counts.kits)),
+		("total", number(counts.total())),
+	])
+}
+
+fn effect_counts_value(counts: &EffectCounts) -> Value {
+	object(vec![
+		("reverb", number(counts.reverb)),
+		("chorus", number(counts.chorus)),
+		("variation", number(counts.variation)),
+		("insertion", number(counts.insertion)),
+		("total", number(counts.total())),
+	])
+}
+
+fn effect_type_value(t: &EffectType) -> Value {
+	object(vec![
+		("block", Value::String(t.block.id().to_string())),
+		("msb", number(t.msb)),
+		("lsb", number(t.lsb)),
+		("name", Value::String(t.name.to_string())),
+		("parameters", Value::Array(
+			effectparams::params(t.msb).iter().map(effect_param_value).collect()
+		)),
+	])
+}
+
+fn effect_param_value(param: &EffectParam) -> Value {
+	object(vec![
+		("number", number(param.number)),
+		("name", Value::String(param.name.to_string())),
+		("min", number(param.min)),
+		("max", number(param.max)),
+		("min_display", Value::String(param.display(param.min))),
+		("max_display", Value::String(param.display(param.max))),
+	])
+}
+
+pub fn voice_value(voice: &Voice) -> Value {
+	object(vec![
+		("msb", number(voice.msb)),
+		("lsb", number(voice.lsb)),
+		("program", number(voice.prg)),
+		("kind", Value::String(voice.kind().id().to_string())),
+		("name", Value::String(voice.name().to_string())),
+	])
+}
+
+fn model_value(model: &Model) -> Value {
+	let voicesets = model.voicesets.iter().map(|set| object(vec![
+		("counts", counts_value(&VoiceCounts::by_kind(set.iter()))),
+		("voices", Value::Array(set.iter().map(voice_value).collect())),
+	])).collect();
+	let effectsets = model.effectsets.iter().map(|set| object(vec![
+		("counts", effect_counts_value(&set.count())),
+		("types", Value::Array(set.iter().map(effect_type_value).collect())),
+	])).collect();
+	object(vec![
+		("name", Value::String(model.name.to_string())),
+		("year", model.year.map_or(Value::Null, number)),
+		("xg_level", model.xg_level.map_or(Value::Null, |l| Value::String(l.to_string()))),
+		("counts", counts_value(&VoiceCounts::by_kind(model.voices()))),
+		("voicesets", Value::Array(voicesets)),
+		("effectsets", Value::Array(effectsets)),
+	])
+}
+
+pub fn export(models: &[&Model]) -> Value {
+	object(vec![
+		("schema", Value::String(SCHEMA.to_string())),
+		("version", number(VERSION)),
+		("models", Value::Array(models.iter().map(|m| model_value(m)).collect())),
+	])
+}
+
+fn field<'a>(obj: &'a Value, key: &str, context: &str) -> Result<&'a Value, Error> {
+	obj.get(key).ok_or_else(|| Error::Schema(format!("{} is missing \"{}\"", context, key)))
+}
+
+fn array<'a>(obj: &'a Value, key: &str, context: &str) -> Result<&'a [Value], Error> {
+	match *field(obj, key, context)? {
+		Value::Array(ref a) => Ok(a),
+		_ => Err(Error::Schema(format!("\"{}\" of {} must be an array", key, context))),
+	}
+}
+
+fn string(obj: &Value, key: &str, context: &str) -> Result<String, Error> {
+	match *field(obj, key, context)? {
+		Value::String(ref s) => Ok(s.clone()),
+		_ => Err(Error::Schema(format!("\"{}\" of {} must be a string", key, context))),
+	}
+}
+
+fn integer(obj: &Value, key: &str, context: &str, min: u32, max: u32) -> Result<u32, Error> {
+	match *field(obj, key, context)? {
+		Value::Number(n) if n.fract() == 0.0 && n >= min as f64 && n <= max as f64 => {
+			Ok(n as u32)
+		}
+		_ => Err(Error::Schema(format!(
+			"\"{}\" of {} must be an integer between {} and {}", key, context, min, max
+		))),
+	}
+}
+
+fn import_voice(v: &Value, context: &str) -> Result<Voice, Error> {
+	Ok(Voice {
+		msb: integer(v, "msb", context, 0, 127)? as u8,
+		lsb: integer(v, "lsb", context, 0, 127)? as u8,
+		prg: integer(v, "program", context, 1, 128)? as u8,
+		name: Box::leak(string(v, "name", context)?.into_boxed_str()),
+	})
+}
+
+fn import_effect_type(t: &Value, context: &str) -> Result<EffectType, Error> {
+	Ok(EffectType {
+		block: string(t, "block", context)?.parse().map_err(Error::Schema)?,
+		msb: integer(t, "msb", context, 0, 127)? as u8,
+		lsb: integer(t, "lsb", context, 0, 127)? as u8,
+		name: Box::leak(string(t, "name", context)?.into_boxed_str()),
+	})
+}
+
+fn import_model(m: &Value, i: usize) -> Result<&'static Model, Error> {
+	let context = format!("model #{}", i + 1);
+	let name = string(m, "name", &context)?;
+	let context = format!("model \"{}\"", name);
+	let year = match *field(m, "year", &context)? {
+		Value::Null => None,
+		_ => Some(integer(m, "year", &context, 0, u16::MAX as u32)? as u16),
+	};
+	let mut voicesets = Vec::new();
+	for (j, set) in array(m, "voicesets", &context)?.iter().enumerate() {
+		let context = format!("voice set #{} of {}", j + 1, context);
+		let mut voices = Vec::new();
+		for (k, v) in array(set, "voices", &context)?.iter().enumerate() {
+			voices.push(import_voice(v, &format!("voice #{} in {}", k + 1, context))?);
+		}
+		voicesets.push(Voices::leak(voices));
+	}
+	let mut effectsets = Vec::new();
+	if m.get("effectsets").is_some() {
+		for (j, set) in array(m, "effectsets", &context)?.iter().enumerate() {
+			let context = format!("effect set #{} of {}", j + 1, context);
+			let mut types = Vec::new();
+			for (k, t) in array(set, "types", &context)?.iter().enumerate() {
+				let context = format!("effect type #{} in {}", k + 1, context);
+				types.push(import_effect_type(t, &context)?);
+			}
+			effectsets.push(EffectTypes::leak(types));
+		}
+	}
+	// Files written before models stated their XG level.
+	let xg_level = match m.get("xg_level") {
+		None => models::guess_xg_level(&voicesets),
+		Some(&Value::Null) => None,
+		Some(_) => Some(string(m, "xg_level", &context)?.parse().map_err(Error::Schema)?),
+	};
+	Ok(Model::leak(name, year, voicesets, effectsets, xg_level))
+}
+
+pub fn import(text: &str) -> Result<Vec<&'static Model>, Error> {
+	let root = Value::parse(text)?;
+	if string(&root, "schema", "the root object")? != SCHEMA {
+		return Err(Error::Schema(format!("\"schema\" must be \"{}\"", SCHEMA)));
+	}
+	let version = integer(&root, "version", "the root object", 0, u32::MAX)?;
+	if version > VERSION {
+		return Err(Error::Schema(format!(
+			"version {} is newer than the supported version {}", version, VERSION
+		)));
+	}
+	array(&root, "models", "the root object")?.iter()
+		.enumerate()
+		.map(|(i, m)| import_model(m, i))
+		.collect()
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<&'static Model>, Error> {
+	import(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn test_model() -> &'static Model {
+		let voices = Voices::leak(vec![
+			Voice { msb: 0, lsb: 0, prg: 1, name: "Say \"Hi\"" },
+			Voice { msb: 0, lsb: 1, prg: 1, name: "Back\\slash" },
+			Voice { msb: 127, lsb: 0, prg: 1, name: "Kit\ttab" },
+		]);
+		Model::leak("Test".to_string(), None, vec![voices], Vec::new(), None)
+	}
+
+	fn voices(model: &Model) -> Vec<(u8, u8, u8, &'static str)> {
+		model.voices().map(|v| (v.msb, v.lsb, v.prg, v.name())).collect()
+	}
+
+	#[test]
+	fn escapes_names() {
+		let text = export(&[test_model()]).to_string();
+		assert!(text.contains(r#""name": "Say \"Hi\"""#), "{}", text);
+		assert!(text.contains(r#""name": "Back\\slash""#), "{}", text);
+		assert!(text.contains(r#""name": "Kit\ttab""#), "{}", text);
+	}
+
+	#[test]
+	fn round_trip() {
+		let original = test_model();
+		let imported = import(&export(&[original]).to_string()).unwrap();
+		assert_eq!(imported.len(), 1);
+		assert_eq!(imported[0].name, "Test");
+		assert_eq!(imported[0].year, None);
+		assert_eq!(voices(imported[0]), voices(original));
+	}
+
+	#[test]
+	fn round_trip_built_in() {
+		let mu80 = models::find("MU80").unwrap();
+		let imported = import(&export(&[mu80]).to_string()).unwrap();
+		let model = imported[0];
+		assert_eq!(model.year, mu80.year);
+		assert_eq!(model.xg_level, mu80.xg_level);
+		assert_eq!(model.voicesets.len(), mu80.voicesets.len());
+		assert_eq!(voices(model), voices(mu80));
+		let types = |m: &Model| -> Vec<_> {
+			m.effect_types().map(|t| (t.block, t.msb, t.lsb, t.name)).collect()
+		};
+		assert_eq!(types(model), types(mu80));
+	}
+}
@@ -0,0 +1,224 @@
+/*
+ * Checks SMFs for common bank select mistakes.
+ */
+
+use std::fmt;
+
+use super::midi::*;
+use super::models::Model;
+use super::smf::{Event, Smf};
+use super::voices::VoiceKind;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+	Warning,
+	Error,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			Severity::Warning => "warning",
+			Severity::Error => "error",
+		})
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Problem {
+	// CC32 without any CC0 on the channel, so the MSB is left at its default.
+	LsbWithoutMsb,
+	// CC0 or CC32 right after a Program Change, which only affects the next
+	// one.
+	BankAfterProgram(u8),
+	// A melodic bank selected on the drum channel after XG System On. GS
+	// keeps the drum channel on drums whatever the MSB, and GM ignores it.
+	MelodicBankOnDrums(Selection),
+	// An XG bank selected without a preceding XG System On.
+	MissingXgSystemOn(Selection),
+	// An address that none of the checked models have.
+	UnknownVoice(Selection),
+}
+
+impl Problem {
+	pub fn severity(&self) -> Severity {
+		match *self {
+			Problem::UnknownVoice(_) => Severity::Error,
+			_ => Severity::Warning,
+		}
+	}
+}
+
+impl fmt::Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Problem::LsbWithoutMsb => write!(f, "bank select LSB sent without MSB"),
+			Problem::BankAfterProgram(cc) => {
+				write!(f, "CC{} sent after the Program Change it was meant for", cc)
+			}
+			Problem::MelodicBankOnDrums(sel) => {
+				write!(f, "{} selects a melodic bank on the drum channel", sel)
+			}
+			Problem::MissingXgSystemOn(sel) => {
+				write!(f, "{} selects an XG bank, but there is no XG System On", sel)
+			}
+			Problem::UnknownVoice(sel) => write!(f, "{} does not exist on any model", sel),
+		}
+	}
+}
+
+pub struct Issue {
+	pub track: usize,
+	pub tick: u64,
+	pub channel: u8,
+	pub problem: Problem,
+}
+
+impl fmt::Display for Issue {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f,
+			"{}: track {:>2}, tick {:>6}, channel {:>2}: {}",
+			self.problem.severity(), self.track + 1, self.tick, self.channel + 1, self.problem
+		)
+	}
+}
+
+// Bank select history of a channel since the last Program Change.
+#[derive(Clone, Copy, Default)]
+struct History {
+	// Whether CC0 was ever sent since the last reset.
+	msb_sent: bool,
+	// Location of a CC32 that is still waiting for its Program Change.
+	lsb_pending: Option<(usize, u64)>,
+	program_tick: Option<u64>,
+	// Bank select following a Program Change on the same tick, which is
+	// only reported if no other Program Change follows before the next note.
+	late: Option<(usize, u64, u8)>,
+}
+
+// Banks that only exist in XG.
+fn is_xg_bank(sel: Selection) -> bool {
+	sel.lsb != 0 || sel.msb == 64 || sel.msb == 126
+}
+
+// Checks all sequences of `smf`, validating selected voices against
+// `models`.
+pub fn lint(smf: &Smf, models: &[&'static Model]) -> Vec<Issue> {
+	let mut ret = Vec::new();
+	for sequence in smf.sequences() {
+		let mut channels: Channels = Default::default();
+		let mut history = [History::default(); 16];
+		let mut xg_on = false;
+		let mut xg_reported = false;
+		for &(track, e) in &sequence {
+			match e.event {
+				Event::Channel { channel, ref msg } => {
+					let change = channels.update(channel, msg);
+					let h = &mut history[channel as usize];
+					match *msg {
+						ChannelMsg::Control { controller, .. }
+							if controller == CC_BANK_MSB || controller == CC_BANK_LSB =>
+						{
+							if controller == CC_BANK_MSB {
+								h.msb_sent = true;
+							} else {
+								h.lsb_pending = Some((track, e.tick));
+							}
+							if h.program_tick == Some(e.tick) && h.late.is_none() {
+								h.late = Some((track, e.tick, controller));
+							}
+						}
+						ChannelMsg::Program(_) => {
+							if let (Some((track, tick)), false) = (h.lsb_pending, h.msb_sent) {
+								let problem = Problem::LsbWithoutMsb;
+								ret.push(Issue { track, tick, channel, problem });
+							}
+							h.lsb_pending = None;
+							h.late = None;
+							h.program_tick = Some(e.tick);
+							let sel = change.unwrap().selection();
+							let kind = VoiceKind::of_msb(sel.msb);
+							let mut problems = Vec::new();
+							if channel == DRUM_CHANNEL && xg_on && kind != VoiceKind::Kit {
+								problems.push(Problem::MelodicBankOnDrums(sel));
+							}
+							if is_xg_bank(sel) && !xg_on && !xg_reported {
+								xg_reported = true;
+								problems.push(Problem::MissingXgSystemOn(sel));
+							}
+							if !models.iter().any(|m| m.find(sel.msb, sel.lsb, sel.prg).is_some()) {
+								problems.push(Problem::UnknownVoice(sel));
+							}
+							for problem in problems {
+								ret.push(Issue { track, tick: e.tick, channel, problem });
+							}
+						}
+						ChannelMsg::NoteOn { vel, .. } if vel > 0 => {
+							if let Some((track, tick, cc)) = h.late.take() {
+								let problem = Problem::BankAfterProgram(cc);
+								ret.push(Issue { track, tick, channel, problem });
+							}
+						}
+						_ => {}
+					}
+				}
+				Event::SysEx(ref data) if is_reset(data) => {
+					channels.reset();
+					history = [History::default(); 16];
+					xg_on = data.get(1) == Some(&0x43);
+				}
+				_ => {}
+			}
+		}
+		for (channel, h) in history.iter().enumerate() {
+			if let Some((track, tick, cc)) = h.late {
+				let problem = Problem::BankAfterProgram(cc);
+				ret.push(Issue { track, tick, channel: channel as u8, problem });
+			}
+		}
+	}
+	// Stable, so that issues of the same event keep their order.
+	ret.sort_by_key(|i| i.tick);
+	ret
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::models;
+	use super::super::smf::{Division, Track, TrackEvent};
+	use super::super::sysex;
+
+	fn smf(events: Vec<Event>) -> Smf {
+		let events = events.into_iter().map(|event| TrackEvent { tick: 0, event }).collect();
+		Smf {
+			format: 0,
+			division: Division::TicksPerQuarter(96),
+			tracks: vec![Track { events }],
+		}
+	}
+
+	fn drums(msg: ChannelMsg) -> Event {
+		Event::Channel { channel: DRUM_CHANNEL, msg }
+	}
+
+	fn problems(reset: Vec<u8>) -> Vec<Problem> {
+		let smf = smf(vec![
+			Event::SysEx(reset),
+			drums(ChannelMsg::Control { controller: CC_BANK_MSB, value: 0 }),
+			drums(ChannelMsg::Program(25)),
+		]);
+		lint(&smf, models::MODELS).into_iter().map(|i| i.problem).collect()
+	}
+
+	#[test]
+	fn gs_drum_channel_ignores_msb() {
+		assert_eq!(problems(sysex::gs_reset(0)), []);
+	}
+
+	#[test]
+	fn xg_melodic_bank_on_drums() {
+		let sel = Selection { msb: 0, lsb: 0, prg: 26 };
+		assert_eq!(problems(sysex::xg_system_on(0)), [Problem::MelodicBankOnDrums(sel)]);
+	}
+}
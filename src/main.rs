@@ -1,7 +1,855 @@
 mod voices;
 mod models;
+mod ins;
+mod json;
+mod csv;
+mod compat;
+mod html;
+mod markdown;
+mod midi;
+mod smf;
+mod remap;
+mod gsxg;
+mod lint;
+mod detect;
+mod corpus;
+mod audition;
+mod drums;
+mod annotate;
+mod sysex;
+mod effects;
+mod effectparams;
+mod xgmap;
+mod decode;
+mod nrpn;
+mod identity;
+mod dump;
+mod device;
+mod monitor;
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+
+use models::Resolution;
+use std::process;
+
+const USAGE: &str = "\
+Usage:
+  yamaha_db
+    Lists the voices of all built-in models.
+  yamaha_db effects [--find <text>] [<model>...]
+    Lists the effect types of the given built-in models (default: all), or
+    only those whose names contain the given text.
+  yamaha_db effect-params <reverb|chorus|variation|insertion> <MMM-LLL>
+                         [<parameter> <value>]
+    Lists the parameters of an effect type, or converts a value of one of
+    them between its raw value and engineering units, as in `1 2.5s`. Plain
+    integers are taken as raw values.
+  yamaha_db ins <file.ins>
+    Lists the voices of all instruments defined in a Cakewalk .ins file.
+  yamaha_db ins-diff <file.ins> <model> [<instrument>]
+    Compares an instrument from a Cakewalk .ins file against a built-in model.
+    <instrument> can be omitted if the file only defines a single one.
+  yamaha_db json-export [<model>...]
+    Writes the given built-in models (default: all) as JSON.
+  yamaha_db json-import <file.json>
+    Lists the voices of all models in a JSON database.
+  yamaha_db csv [--tsv] [--base <0|1>] [--columns <column,...>] [<model>...]
+    Writes one row per unique voice address of the given built-in models
+    (default: all). --base sets the number of the first program (default: 1).
+    Columns: msb, lsb, program, name, kind, models, other_names (default: all).
+  yamaha_db html <directory> [<model>...]
+    Generates static HTML voice lists for the given built-in models
+    (default: all).
+  yamaha_db markdown [--sets] [<model>...]
+    Writes Markdown voice tables for the given built-in models (default: all).
+    --sets writes separate tables for each voice set of a model.
+  yamaha_db smf <file.mid> <model>
+    Detects whether a Standard MIDI File was written for GM, GS or XG, and
+    lists the voices selected on each channel over time, as resolved on a
+    built-in model.
+  yamaha_db compat <file.mid>
+    Ranks all built-in models by how faithfully they can play the voices
+    used in a Standard MIDI File.
+  yamaha_db remap <in.mid> <out.mid> <source model> <target model> [<mapping>]
+    Rewrites the voice selections of a Standard MIDI File written for the
+    source model to the closest voices available on the target model.
+    <mapping> is a text file with lines like `000-041-001 = 000-000-089`,
+    which take precedence over the automatic substitutions.
+  yamaha_db convert <in.mid> <out.mid> <gs|xg>
+    Converts a Standard MIDI File written for Roland GS to Yamaha XG, or the
+    other way around, including drum kits and rhythm part assignments.
+  yamaha_db lint <file.mid> [<model>...]
+    Checks a Standard MIDI File for bank select mistakes, validating the
+    selected voices against the given built-in models (default: all).
+  yamaha_db corpus [--json] <directory> [<model>...]
+    Analyses all MIDI files below a directory and reports aggregate voice
+    usage and per-file compatibility with the given built-in models
+    (default: all), as text or JSON.
+  yamaha_db audition <out.mid> <model> [--bank <MMM-LLL>] [--programs <N-M>]
+                     [--kind <instrument|sfx|kit>]
+    Writes a Standard MIDI File that plays a short phrase on every voice of
+    a built-in model, optionally restricted to a bank, a range of programs
+    or a kind of voice.
+  yamaha_db drum-audition <out.mid> <model> [--bank <MMM-LLL>] [--programs <N-M>]
+    Writes a Standard MIDI File that plays every note of each drum kit of a
+    built-in model, with markers naming the instrument of each note.
+  yamaha_db annotate <in.mid> <out.mid> <model>
+    Adds markers with the voice names, as resolved on a built-in model, to
+    every Program Change of a Standard MIDI File, and names the tracks that
+    only play a single voice.
+  yamaha_db sysex reset <gm|gs|xg>
+  yamaha_db sysex identity
+  yamaha_db sysex voice <model> <MMM-LLL-PPP> [<part>]
+  yamaha_db sysex dump-request <hh> <mm> <ll>
+  yamaha_db sysex param <hh> <mm> <ll> <data>...
+  yamaha_db sysex bulk <hh> <mm> <ll> <data>...
+    Writes System Exclusive messages in hex: a reset, an Identity Request
+    to all devices, the XG parameter changes that select a voice of a
+    built-in model on a part (1-16, default: 1), an XG dump request for the
+    block at an address, or an XG parameter change or bulk dump of hex data
+    at an address.
+  yamaha_db decode [--model <model>] <file.mid>
+  yamaha_db decode [--model <model>] --hex <byte>...
+    Explains the System Exclusive messages of a Standard MIDI File, or a
+    message given in hex, resolving voices on a built-in model (default: the
+    first XG model).
+  yamaha_db identify <byte>...
+    Detects the built-in model that sent an Identity Reply, or the kind of
+    module from the model ID of a Yamaha bulk dump or parameter change,
+    given in hex.
+  yamaha_db dump <file.syx> [<model>]
+    Lists the bank and program of each part set by the XG Multi Part bulk
+    dumps in a .syx file, with the voices resolved on a built-in model
+    (default: detected from the file, or the first XG model), and reports bad
+    checksums and byte counts.
+  yamaha_db device <model> <file>
+  yamaha_db device <model> --hex <byte>...
+    Sends raw MIDI bytes from a file, or given in hex, to a virtual XG
+    module emulating a built-in model as device 1. Prints the messages it
+    sends back, and then the voices of the parts that were set and the
+    effect types.
+  yamaha_db monitor <model> [<file>]
+    Reads a MIDI byte stream from a file or standard input, as raw bytes or
+    as hex text like `amidi -d` prints, and prints a line whenever the voice
+    of a channel changes, resolved on a built-in model.
+  yamaha_db nrpn list [<model>]
+  yamaha_db nrpn <model> <channel> <name> [<note>] <value>
+    Lists the XG voice edit NRPNs, checking that a built-in model supports
+    them, or writes the Control Changes in hex that set an NRPN on a channel
+    (1-16). Drum NRPNs need the note of the instrument to edit.
+  yamaha_db xg-params [<model>]
+    Lists the XG parameter map with ranges and defaults, or only the
+    parameters available on a built-in model.
+  yamaha_db xg-param <name>
+  yamaha_db xg-param <hh> <mm> <ll>
+    Shows an XG parameter by name or address, and the built-in models that
+    have it.";
+
+type CmdResult = Result<(), Box<dyn Error>>;
+
+fn model_arg(name: &str) -> Result<&'static models::Model, Box<dyn Error>> {
+	models::find(name).ok_or_else(|| format!("unknown model: {}", name).into())
+}
+
+// Resolves built-in model names, defaulting to all models.
+fn models_arg(names: &[&str]) -> Result<Vec<&'static models::Model>, Box<dyn Error>> {
+	match names {
+		[] => Ok(models::MODELS.to_vec()),
+		names => names.iter().map(|n| model_arg(n)).collect(),
+	}
+}
+
+fn cmd_list() -> CmdResult {
+	for model in models::MODELS {
+		println!("{}", model);
+	}
+	Ok(())
+}
+
+fn cmd_effects(args: &[&str]) -> CmdResult {
+	let (find, names) = match *args {
+		["--find", text, ref names @ ..] => (Some(text.to_lowercase()), names),
+		ref names => (None, names),
+	};
+	for model in models_arg(names)? {
+		println!("{}:", model.title());
+		let types = model.effect_types()
+			.filter(|t| find.as_ref().is_none_or(|find| t.name.to_lowercase().contains(find)));
+		for t in types {
+			println!("{}", t);
+		}
+		if find.is_none() {
+			println!("{}", model.effectcount());
+		}
+		println!();
+	}
+	Ok(())
+}
+
+fn cmd_effect_params(block: &str, effect: &str, convert: Option<(&str, &str)>) -> CmdResult {
+	let block: effects::EffectBlock = block.parse()?;
+	let (msb, lsb) = effect.split_once('-')
+		.and_then(|(msb, lsb)| Some((msb.parse::<u8>().ok()?, lsb.parse::<u8>().ok()?)))
+		.ok_or_else(|| format!("invalid effect type: {}", effect))?;
+	let t = block.find(msb, lsb)
+		.ok_or_else(|| format!("unknown {} type {}", block.id(), effect))?;
+	match convert {
+		None => {
+			println!("{} {}:", block, t.name);
+			for param in effectparams::params(msb) {
+				println!("{}", param);
+			}
+		}
+		Some((number, value)) => {
+			let param = number.parse().ok()
+				.and_then(|number| effectparams::param(msb, number))
+				.ok_or_else(|| format!("{} has no parameter {}", t.name, number))?;
+			let raw = match value.parse::<u32>() {
+				Ok(raw) if raw >= param.min && raw <= param.max => raw,
+				Ok(_) => return Err(format!(
+					"{} is out of range ({} to {})", value, param.min, param.max
+				).into()),
+				Err(_) => param.parse(value)
+					.ok_or_else(|| format!("invalid value for {}: {}", param.name, value))?,
+			};
+			println!("{}: {} = {}", param.name, raw, param.display(raw));
+		}
+	}
+	Ok(())
+}
+
+fn cmd_ins(fn_ins: &str) -> CmdResult {
+	let file = ins::InsFile::load(fn_ins)?;
+	for instrument in &file.instruments {
+		println!("{}", instrument.to_model());
+	}
+	Ok(())
+}
+
+fn cmd_ins_diff(fn_ins: &str, model: &str, instrument: Option<&str>) -> CmdResult {
+	let model = model_arg(model)?;
+	let file = ins::InsFile::load(fn_ins)?;
+	let instrument = match instrument {
+		Some(name) => file.instrument(name)
+			.ok_or_else(|| format!("{} does not define [{}]", fn_ins, name))?,
+		None if file.instruments.len() == 1 => &file.instruments[0],
+		None => {
+			let names: Vec<&str> = file.instruments.iter()
+				.map(|i| i.name.as_str())
+				.collect();
+			return Err(format!(
+				"{} defines multiple instruments, pick one of:\n{}",
+				fn_ins, names.join("\n")
+			).into());
+		}
+	};
+	let discrepancies = ins::diff(instrument, model);
+	for it in &discrepancies {
+		println!("{}", it);
+	}
+	println!(
+		"{} discrepancies between [{}] and {}.",
+		discrepancies.len(), instrument.name, model.name
+	);
+	Ok(())
+}
+
+fn cmd_json_export(names: &[&str]) -> CmdResult {
+	println!("{}", json::export(&models_arg(names)?));
+	Ok(())
+}
+
+fn cmd_json_import(fn_json: &str) -> CmdResult {
+	for model in json::load(fn_json)? {
+		println!("{}", model);
+	}
+	Ok(())
+}
+
+fn cmd_csv(mut args: &[&str]) -> CmdResult {
+	let mut opts: csv::Options = Default::default();
+	loop {
+		match *args {
+			["--tsv", ref rest @ ..] => {
+				opts.format = csv::Format::Tsv;
+				args = rest;
+			}
+			["--base", base, ref rest @ ..] => {
+				opts.program_base = match base {
+					"0" => 0,
+					"1" => 1,
+					_ => return Err(format!("invalid numbering base: {}", base).into()),
+				};
+				args = rest;
+			}
+			["--columns", columns, ref rest @ ..] => {
+				opts.columns = columns.split(',').map(str::parse).collect::<Result<_, _>>()?;
+				args = rest;
+			}
+			_ => break,
+		}
+	}
+	let stdout = io::stdout();
+	csv::export(&mut stdout.lock(), &models_arg(args)?, &opts)?;
+	Ok(())
+}
+
+fn cmd_html(dir: &str, names: &[&str]) -> CmdResult {
+	html::generate(Path::new(dir), &models_arg(names)?)?;
+	Ok(())
+}
+
+fn cmd_markdown(names: &[&str]) -> CmdResult {
+	let (sets, names) = match names {
+		["--sets", ref rest @ ..] => (true, rest),
+		names => (false, names),
+	};
+	for model in models_arg(names)? {
+		if sets {
+			for (i, set) in model.voicesets.iter().enumerate() {
+				println!("## {}, voice set {}\n", model.title(), i + 1);
+				println!("{}\n", set.voicecount());
+				print!("{}", markdown::Markdown(*set));
+			}
+		} else {
+			print!("{}", markdown::Markdown(model));
+		}
+	}
+	Ok(())
+}
+
+fn cmd_smf(fn_smf: &str, model: &str) -> CmdResult {
+	let model = model_arg(model)?;
+	let smf = smf::Smf::load(fn_smf)?;
+	print!("Standard: {}", detect::detect(&smf));
+	let sequences = smf.sequences();
+	for (i, sequence) in sequences.iter().enumerate() {
+		if sequences.len() > 1 {
+			println!("Sequence {}:", i + 1);
+		}
+		let tempo = smf::TempoMap::new(smf.division, sequence);
+		let changes = smf::voice_changes(sequence);
+		for channel in 0..16 {
+			let mut changes = changes.iter().filter(|c| c.channel == channel).peekable();
+			if changes.peek().is_none() {
+				continue;
+			}
+			println!("Channel {}:", channel + 1);
+			for it in changes {
+				let (sel, default) = match it.change {
+					midi::Change::Selected(sel) => (sel, ""),
+					midi::Change::Defaulted(sel) => (sel, " (default)"),
+				};
+				let voice = annotate::describe(model, sel);
+				println!(
+					"\t{} (tick {:>6}, track {:>2}) {}{}",
+					smf::Timestamp(tempo.seconds(it.tick)), it.tick, it.track + 1, voice, default
+				);
+			}
+		}
+	}
+	Ok(())
+}
+
+fn cmd_compat(fn_smf: &str) -> CmdResult {
+	let smf = smf::Smf::load(fn_smf)?;
+	let ranking = compat::rank(&smf, models::MODELS);
+	print!("{}", compat::Table(&ranking));
+	for c in &ranking {
+		for sel in &c.fallback {
+			if let Resolution::Fallback(voice) = c.model.resolve(sel.msb, sel.lsb, sel.prg) {
+				println!("{}: {} -> {}", c.model.name, sel, voice);
+			}
+		}
+		for sel in &c.missing {
+			println!("{}: {} is missing", c.model.name, sel);
+		}
+	}
+	Ok(())
+}
+
+fn cmd_remap(
+	fn_in: &str, fn_out: &str, source: &str, target: &str, fn_mapping: Option<&str>
+) -> CmdResult {
+	let mapping = match fn_mapping {
+		Some(fn_mapping) => remap::Mapping::load(fn_mapping)?,
+		None => Default::default(),
+	};
+	let remapper = remap::Remapper {
+		source: model_arg(source)?,
+		target: model_arg(target)?,
+		mapping: &mapping,
+	};
+	mapping.check(remapper.target)?;
+	let (smf, log) = remapper.remap(&smf::Smf::load(fn_in)?);
+	for it in &log {
+		println!("{}", it);
+	}
+	smf.save(fn_out)?;
+	Ok(())
+}
+
+fn cmd_convert(fn_in: &str, fn_out: &str, to: &str) -> CmdResult {
+	let to = match to {
+		"gs" => gsxg::Standard::Gs,
+		"xg" => gsxg::Standard::Xg,
+		_ => return Err(format!("unknown standard: {} (expected gs or xg)", to).into()),
+	};
+	let (smf, log) = gsxg::convert(&smf::Smf::load(fn_in)?, to);
+	for it in &log {
+		println!("{}", it);
+	}
+	smf.save(fn_out)?;
+	Ok(())
+}
+
+fn cmd_lint(fn_smf: &str, names: &[&str]) -> CmdResult {
+	let models = models_arg(names)?;
+	for issue in lint::lint(&smf::Smf::load(fn_smf)?, &models) {
+		println!("{}", issue);
+	}
+	Ok(())
+}
+
+fn cmd_corpus(dir: &str, names: &[&str], as_json: bool) -> CmdResult {
+	let models = models_arg(names)?;
+	let scan = corpus::scan(dir)?;
+	for (path, e) in &scan.skipped {
+		eprintln!("warning: skipped {}: {}", path.display(), e);
+	}
+	let report = corpus::analyse(&scan.files, &models);
+	if as_json {
+		println!("{}", report.to_json());
+	} else {
+		print!("{}", report);
+	}
+	Ok(())
+}
+
+fn cmd_audition(fn_out: &str, model: &str, mut args: &[&str], kits: bool) -> CmdResult {
+	let mut opts: audition::Options = Default::default();
+	let pair = |s: &str| -> Option<(u8, u8)> {
+		let (a, b) = s.split_once('-')?;
+		Some((a.parse().ok()?, b.parse().ok()?))
+	};
+	loop {
+		match *args {
+			["--bank", bank, ref rest @ ..] => {
+				opts.bank = Some(pair(bank).ok_or_else(|| format!("invalid bank: {}", bank))?);
+				args = rest;
+			}
+			["--programs", programs, ref rest @ ..] => {
+				opts.programs = match pair(programs) {
+					Some((first, last)) if 1 <= first && first <= last && last <= 128 => first..=last,
+					_ => return Err(format!("invalid program range: {}", programs).into()),
+				};
+				args = rest;
+			}
+			["--kind", kind, ref rest @ ..] if !kits => {
+				opts.kind = Some(kind.parse()?);
+				args = rest;
+			}
+			[] => break,
+			_ => return Err(format!("unknown option: {}", args[0]).into()),
+		}
+	}
+	let model = model_arg(model)?;
+	let smf = if kits {
+		audition::generate_kits(model, &opts)
+	} else {
+		audition::generate(model, &opts)
+	};
+	smf.save(fn_out)?;
+	Ok(())
+}
+
+fn cmd_annotate(fn_in: &str, fn_out: &str, model: &str) -> CmdResult {
+	annotate::annotate(&smf::Smf::load(fn_in)?, model_arg(model)?).save(fn_out)?;
+	Ok(())
+}
+
+// Data bytes given in hex, as in "7F".
+fn hex_args(args: &[&str]) -> Result<Vec<u8>, Box<dyn Error>> {
+	args.iter()
+		.map(|s| match u8::from_str_radix(s, 16) {
+			Ok(b) if b < 0x80 => Ok(b),
+			_ => Err(format!("invalid data byte: {}", s).into()),
+		})
+		.collect()
+}
+
+fn address_arg(args: &[&str]) -> Result<sysex::Address, Box<dyn Error>> {
+	let bytes = hex_args(args)?;
+	Ok(sysex::Address { high: bytes[0], mid: bytes[1], low: bytes[2] })
+}
+
+fn cmd_sysex(args: &[&str]) -> CmdResult {
+	let messages = match *args {
+		["reset", "gm"] => vec![sysex::gm_system_on()],
+		["reset", "gs"] => vec![sysex::gs_reset(0)],
+		["reset", "xg"] => vec![sysex::xg_system_on(0)],
+		["identity"] => vec![sysex::identity_request(0x7F)],
+		["voice", model, sel, ref part @ ..] if part.len() <= 1 => {
+			let model = model_arg(model)?;
+			if !model.is_xg() {
+				return Err(format!("{} does not support XG parameter changes", model.name).into());
+			}
+			let sel: midi::Selection = sel.parse()?;
+			let voice = model.find(sel.msb, sel.lsb, sel.prg)
+				.ok_or_else(|| format!("no voice {} on {}", sel, model.name))?;
+			let part = match part.first() {
+				Some(part) => match part.parse::<u8>() {
+					Ok(part @ 1..=16) => part - 1,
+					_ => return Err(format!("invalid part: {}", part).into()),
+				},
+				None => 0,
+			};
+			let mut ret = vec![sysex::xg_system_on(0)];
+			ret.extend(sysex::xg_voice_setup(0, part, voice));
+			ret
+		}
+		["dump-request", h, m, l] => vec![sysex::xg_dump_request(0, address_arg(&[h, m, l])?)],
+		["param", h, m, l, ref data @ ..] if !data.is_empty() => {
+			vec![sysex::xg_parameter_change(0, address_arg(&[h, m, l])?, &hex_args(data)?)]
+		}
+		["bulk", h, m, l, ref data @ ..] if !data.is_empty() => {
+			vec![sysex::xg_bulk_dump(0, address_arg(&[h, m, l])?, &hex_args(data)?)]
+		}
+		_ => {
+			eprintln!("{}", USAGE);
+			process::exit(2);
+		}
+	};
+	for it in &messages {
+		println!("{}", sysex::Hex(it));
+	}
+	Ok(())
+}
+
+// A whole message in hex, which may be given as a single argument.
+fn message_args(args: &[&str]) -> Result<Vec<u8>, Box<dyn Error>> {
+	args.iter()
+		.flat_map(|s| s.split_whitespace())
+		.map(|s| u8::from_str_radix(s, 16).map_err(|_| format!("invalid byte: {}", s).into()))
+		.collect()
+}
+
+fn cmd_decode(mut args: &[&str]) -> CmdResult {
+	let mut model = models::MODELS.iter().cloned().find(|m| m.is_xg()).unwrap();
+	if let ["--model", name, ref rest @ ..] = *args {
+		model = model_arg(name)?;
+		args = rest;
+	}
+	let mut decoder = decode::Decoder::new(model);
+	match *args {
+		["--hex", ref bytes @ ..] if !bytes.is_empty() => {
+			let sysex = message_args(bytes)?;
+			for line in decoder.decode(&sysex) {
+				println!("{}", line);
+			}
+		}
+		[fn_smf] => {
+			let smf = smf::Smf::load(fn_smf)?;
+			for sequence in smf.sequences() {
+				for (track, e) in sequence {
+					if let smf::Event::SysEx(ref data) = e.event {
+						for line in decoder.decode(data) {
+							println!("tick {:>6}, track {:>2}: {}", e.tick, track + 1, line);
+						}
+					}
+				}
+			}
+		}
+		_ => {
+			eprintln!("{}", USAGE);
+			process::exit(2);
+		}
+	}
+	Ok(())
+}
+
+fn cmd_identify(args: &[&str]) -> CmdResult {
+	let detection = identity::detect(&message_args(args)?)?;
+	println!("{}", detection);
+	Ok(())
+}
+
+fn cmd_dump(fn_syx: &str, model: Option<&str>) -> CmdResult {
+	let dump = dump::Dump::parse(&fs::read(fn_syx)?);
+	for warning in &dump.warnings {
+		println!("warning: {}", warning);
+	}
+	let model = match (model, &dump.detection) {
+		(Some(name), _) => model_arg(name)?,
+		(None, Some(detection)) => {
+			println!("Model: {}", detection);
+			match detection.model {
+				Some(model) => model,
+				None => {
+					let model = models::MODELS.iter().cloned().find(|m| m.is_xg()).unwrap();
+					println!("Resolving voices on {}, the first XG model", model.name);
+					model
+				}
+			}
+		}
+		(None, None) => return Err("cannot detect the model, please give one".into()),
+	};
+	let parts = dump.dumped_parts()
+		.ok_or_else(|| format!("{} has no valid Multi Part data", fn_syx))?;
+	print!("{}", dump::PartTable { parts, model });
+	for (setup, notes) in dump.drum_notes.iter().enumerate() {
+		let notes = notes.len();
+		if notes > 0 {
+			let plural = if notes == 1 { "" } else { "s" };
+			println!("Drum setup {}: {} note{} edited", setup + 1, notes, plural);
+		}
+	}
+	Ok(())
+}
+
+fn cmd_device(model: &str, args: &[&str]) -> CmdResult {
+	let bytes = match *args {
+		["--hex", ref bytes @ ..] if !bytes.is_empty() => message_args(bytes)?,
+		[fn_in] => fs::read(fn_in)?,
+		_ => {
+			eprintln!("{}", USAGE);
+			process::exit(2);
+		}
+	};
+	let mut device = device::Device::new(model_arg(model)?, 0)?;
+	for reply in device.send(&bytes) {
+		println!("reply: {}", sysex::Hex(&reply));
+	}
+	let parts = device.parts();
+	let parts = parts.iter().filter(|p| p.touched).collect();
+	print!("{}", dump::PartTable { parts, model: device.model });
+	for &block in &effects::EFFECT_BLOCKS {
+		if let Some((msb, lsb)) = device.effect(block) {
+			match device.model.find_effect(block, msb, lsb) {
+				Some(t) => println!("{}", t),
+				None => println!("{:<9} {:03}-{:03} (unknown)", block, msb, lsb),
+			}
+		}
+	}
+	println!("{} messages ignored, {} bytes dropped", device.ignored, device.dropped());
+	Ok(())
+}
+
+fn cmd_monitor(model: &str, fn_in: Option<&str>) -> CmdResult {
+	let mut monitor = monitor::Monitor::new(model_arg(model)?);
+	let input: Box<dyn Read> = match fn_in {
+		Some(fn_in) => Box::new(fs::File::open(fn_in)?),
+		None => Box::new(io::stdin()),
+	};
+	let mut input = io::BufReader::new(input);
+	if monitor::is_text(input.fill_buf()?) {
+		for line in input.lines() {
+			for byte in monitor::hex_line(&line?).unwrap_or_default() {
+				if let Some(line) = monitor.feed(byte) {
+					println!("{}", line);
+				}
+			}
+		}
+	} else {
+		loop {
+			let len = {
+				let buf = input.fill_buf()?;
+				for &byte in buf {
+					if let Some(line) = monitor.feed(byte) {
+						println!("{}", line);
+					}
+				}
+				buf.len()
+			};
+			if len == 0 {
+				break;
+			}
+			input.consume(len);
+		}
+	}
+	if monitor.dropped() > 0 {
+		eprintln!("{} bytes were not part of a complete message", monitor.dropped());
+	}
+	Ok(())
+}
+
+fn cmd_nrpn(args: &[&str]) -> CmdResult {
+	let (model, channel, name, note, value) = match *args {
+		["list", ref names @ ..] if names.len() <= 1 => {
+			let model = names.first().map(|name| model_arg(name)).transpose()?;
+			if let Some(model) = model {
+				if !model.is_xg() {
+					return Err(format!("{} does not support XG NRPNs", model.name).into());
+				}
+			}
+			for n in nrpn::NRPNS {
+				let lsb = n.lsb.map_or_else(|| "rr".to_string(), |lsb| format!("{:02X}", lsb));
+				println!("{:02X} {}  {}, {} to {}", n.msb, lsb, n.name,
+					n.format.display(n.min as u32), n.format.display(n.max as u32)
+				);
+			}
+			return Ok(());
+		}
+		[model, channel, name, value] => (model, channel, name, None, value),
+		[model, channel, name, note, value] => (model, channel, name, Some(note), value),
+		_ => {
+			eprintln!("{}", USAGE);
+			process::exit(2);
+		}
+	};
+	let model = model_arg(model)?;
+	let channel = match channel.parse::<u8>() {
+		Ok(channel @ 1..=16) => channel - 1,
+		_ => return Err(format!("invalid channel: {}", channel).into()),
+	};
+	let n = nrpn::find(name).ok_or_else(|| format!("unknown NRPN: {}", name))?;
+	let note = note.map(|note| note.parse::<u8>().map_err(|_| format!("invalid note: {}", note)))
+		.transpose()?;
+	let value = value.parse::<u8>().map_err(|_| format!("invalid value: {}", value))?;
+	for msg in n.messages(model, note, value)? {
+		let (status, mut bytes) = msg.encode(channel);
+		bytes.insert(0, status);
+		println!("{}", sysex::Hex(&bytes));
+	}
+	Ok(())
+}
+
+// As in "08 pp 0B  Multi Part: Volume, 0 to 127, default 100".
+fn print_param(address: &str, block: &str, param: &xgmap::Param) {
+	let default = match param.default {
+		Some(v) => param.format.display(v),
+		None => "varies".to_string(),
+	};
+	let mut line = match param.format {
+		xgmap::Format::Trigger => format!("{}  {}: {}, action", address, block, param.name),
+		_ => format!(
+			"{}  {}: {}, {}, default {}", address, block, param.name, param.range(), default
+		),
+	};
+	if param.size > 1 {
+		line += &format!(", {} bytes", param.size);
+	}
+	if param.level > xgmap::Level::Xg1 {
+		line += &format!(" [{}]", param.level);
+	}
+	println!("{}", line);
+}
+
+fn cmd_xg_params(model: Option<&str>) -> CmdResult {
+	let model = model.map(model_arg).transpose()?;
+	if let Some(model) = model {
+		if !model.is_xg() {
+			return Err(format!("{} is not an XG module", model.name).into());
+		}
+	}
+	for &block in &xgmap::BLOCKS {
+		for param in block.params() {
+			if model.is_none_or(|model| param.available_on(model)) {
+				let address = format!("{} {:02X}", block.pattern(), param.offset);
+				print_param(&address, block.kind(), param);
+			}
+		}
+	}
+	Ok(())
+}
+
+fn cmd_xg_param(args: &[&str]) -> CmdResult {
+	let found: Vec<(String, String, &xgmap::Param)> = match *args {
+		[h, m, l] if h.len() == 2 && m.len() == 2 && l.len() == 2 => {
+			let address = address_arg(&[h, m, l])?;
+			let (block, param) = xgmap::find(address)
+				.ok_or_else(|| format!("no XG parameter at {}", address))?;
+			vec![(address.to_string(), block.to_string(), param)]
+		}
+		_ => {
+			let name = args.join(" ");
+			let found = xgmap::find_by_name(&name);
+			if found.is_empty() {
+				return Err(format!("no XG parameter called {}", name).into());
+			}
+			found.into_iter()
+				.map(|(block, param)| {
+					let address = format!("{} {:02X}", block.pattern(), param.offset);
+					(address, block.kind().to_string(), param)
+				})
+				.collect()
+		}
+	};
+	for (address, block, param) in found {
+		print_param(&address, &block, param);
+		let models: Vec<&str> = models::MODELS.iter()
+			.filter(|m| param.available_on(m))
+			.map(|m| m.name)
+			.collect();
+		if models.is_empty() {
+			println!("    not available on any built-in model");
+		} else {
+			println!("    available on {}", models.join(", "));
+		}
+	}
+	Ok(())
+}
 
 fn main() {
-	println!("{}", models::MU5);
-	println!("{}", models::MU80);
+	let args: Vec<String> = env::args().skip(1).collect();
+	let args: Vec<&str> = args.iter().map(String::as_str).collect();
+	let ret = match args[..] {
+		[] => cmd_list(),
+		["effects", ref args @ ..] => cmd_effects(args),
+		["effect-params", block, effect] => cmd_effect_params(block, effect, None),
+		["effect-params", block, effect, number, value] => {
+			cmd_effect_params(block, effect, Some((number, value)))
+		}
+		["ins", fn_ins] => cmd_ins(fn_ins),
+		["ins-diff", fn_ins, model] => cmd_ins_diff(fn_ins, model, None),
+		["ins-diff", fn_ins, model, instrument] => {
+			cmd_ins_diff(fn_ins, model, Some(instrument))
+		}
+		["json-export", ref names @ ..] => cmd_json_export(names),
+		["json-import", fn_json] => cmd_json_import(fn_json),
+		["csv", ref args @ ..] => cmd_csv(args),
+		["html", dir, ref names @ ..] => cmd_html(dir, names),
+		["markdown", ref names @ ..] => cmd_markdown(names),
+		["smf", fn_smf, model] => cmd_smf(fn_smf, model),
+		["compat", fn_smf] => cmd_compat(fn_smf),
+		["remap", fn_in, fn_out, source, target] => {
+			cmd_remap(fn_in, fn_out, source, target, None)
+		}
+		["remap", fn_in, fn_out, source, target, fn_mapping] => {
+			cmd_remap(fn_in, fn_out, source, target, Some(fn_mapping))
+		}
+		["convert", fn_in, fn_out, to] => cmd_convert(fn_in, fn_out, to),
+		["lint", fn_smf, ref names @ ..] => cmd_lint(fn_smf, names),
+		["corpus", "--json", dir, ref names @ ..] => cmd_corpus(dir, names, true),
+		["corpus", dir, ref names @ ..] => cmd_corpus(dir, names, false),
+		["audition", fn_out, model, ref args @ ..] => cmd_audition(fn_out, model, args, false),
+		["drum-audition", fn_out, model, ref args @ ..] => {
+			cmd_audition(fn_out, model, args, true)
+		}
+		["annotate", fn_in, fn_out, model] => cmd_annotate(fn_in, fn_out, model),
+		["sysex", ref args @ ..] => cmd_sysex(args),
+		["decode", ref args @ ..] => cmd_decode(args),
+		["identify", ref args @ ..] if !args.is_empty() => cmd_identify(args),
+		["dump", fn_syx] => cmd_dump(fn_syx, None),
+		["dump", fn_syx, model] => cmd_dump(fn_syx, Some(model)),
+		["device", model, ref args @ ..] => cmd_device(model, args),
+		["monitor", model] => cmd_monitor(model, None),
+		["monitor", model, fn_in] => cmd_monitor(model, Some(fn_in)),
+		["nrpn", ref args @ ..] => cmd_nrpn(args),
+		["xg-params"] => cmd_xg_params(None),
+		["xg-params", model] => cmd_xg_params(Some(model)),
+		["xg-param", ref args @ ..] if !args.is_empty() => cmd_xg_param(args),
+		_ => {
+			eprintln!("{}", USAGE);
+			process::exit(2);
+		}
+	};
+	if let Err(e) = ret {
+		eprintln!("error: {}", e);
+		process::exit(1);
+	}
 }
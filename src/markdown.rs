@@ -0,0 +1,102 @@
+/*
+ * Markdown voice tables, laid out like the voice lists in Yamaha's manuals:
+ * instruments grouped by General MIDI category with one column per bank,
+ * followed by separate tables for sound effects and drum kits.
+ */
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use super::models::Model;
+use super::voices::*;
+
+// Alternative to the plain `Display` output of `Model` and `Voices`.
+pub struct Markdown<'a, T: 'a>(pub &'a T);
+
+struct Cell<'a>(&'a str);
+
+impl<'a> fmt::Display for Cell<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.0.replace('|', "\\|"))
+	}
+}
+
+// Program number grid with one column per bank.
+fn grid(f: &mut fmt::Formatter, voices: &[&Voice]) -> fmt::Result {
+	let banks: BTreeSet<(u8, u8)> = voices.iter().map(|v| (v.msb, v.lsb)).collect();
+	let prgs: BTreeSet<u8> = voices.iter().map(|v| v.prg).collect();
+	write!(f, "| Program |")?;
+	for &(msb, lsb) in &banks {
+		match msb {
+			0 => write!(f, " LSB {:03} |", lsb)?,
+			msb => write!(f, " MSB {:03} LSB {:03} |", msb, lsb)?,
+		}
+	}
+	write!(f, "\n|--------:|")?;
+	for _ in &banks {
+		write!(f, "---|")?;
+	}
+	writeln!(f)?;
+	for &prg in &prgs {
+		write!(f, "| {:03} |", prg)?;
+		for &(msb, lsb) in &banks {
+			match voices.iter().find(|v| v.msb == msb && v.lsb == lsb && v.prg == prg) {
+				Some(v) => write!(f, " {} |", Cell(v.name()))?,
+				None => write!(f, " |")?,
+			}
+		}
+		writeln!(f)?;
+	}
+	writeln!(f)
+}
+
+fn kits(f: &mut fmt::Formatter, voices: &[&Voice]) -> fmt::Result {
+	writeln!(f, "| MSB | LSB | Program | Kit |")?;
+	writeln!(f, "|----:|----:|--------:|-----|")?;
+	for v in voices {
+		writeln!(f, "| {:03} | {:03} | {:03} | {} |", v.msb, v.lsb, v.prg, Cell(v.name()))?;
+	}
+	writeln!(f)
+}
+
+fn tables(f: &mut fmt::Formatter, voices: &[&Voice]) -> fmt::Result {
+	let of_kind = |kind| -> Vec<&Voice> {
+		voices.iter().cloned().filter(|v| v.kind() == kind).collect()
+	};
+	let instruments = of_kind(VoiceKind::Instrument);
+	for category in GM_CATEGORIES.iter() {
+		let in_category: Vec<&Voice> = instruments.iter()
+			.cloned()
+			.filter(|v| gm_category(v.prg) == *category)
+			.collect();
+		if !in_category.is_empty() {
+			writeln!(f, "### {}\n", category)?;
+			grid(f, &in_category)?;
+		}
+	}
+	let sfx = of_kind(VoiceKind::Sfx);
+	if !sfx.is_empty() {
+		writeln!(f, "### SFX\n")?;
+		grid(f, &sfx)?;
+	}
+	let kit_voices = of_kind(VoiceKind::Kit);
+	if !kit_voices.is_empty() {
+		writeln!(f, "### Drum kits\n")?;
+		kits(f, &kit_voices)?;
+	}
+	Ok(())
+}
+
+impl<'a> fmt::Display for Markdown<'a, Voices> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		tables(f, &self.0.iter().collect::<Vec<_>>())
+	}
+}
+
+impl<'a> fmt::Display for Markdown<'a, Model> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "## {}\n", self.0.title())?;
+		writeln!(f, "{}\n", VoiceCounts::by_kind(self.0.voices()))?;
+		tables(f, &self.0.voices().collect::<Vec<_>>())
+	}
+}
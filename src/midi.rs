@@ -0,0 +1,297 @@
+/*
+ * MIDI channel messages and per-channel voice selection state.
+ */
+
+use std::fmt;
+use std::str::FromStr;
+
+pub const CC_BANK_MSB: u8 = 0;
+pub const CC_BANK_LSB: u8 = 32;
+
+// 0-based index of the channel that defaults to drums.
+pub const DRUM_CHANNEL: u8 = 9;
+
+// MSB of the drum kit bank, as initially selected on the drum channel.
+pub const DRUM_MSB: u8 = 127;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChannelMsg {
+	NoteOff { key: u8, vel: u8 },
+	NoteOn { key: u8, vel: u8 },
+	KeyPressure { key: u8, pressure: u8 },
+	Control { controller: u8, value: u8 },
+	Program(u8), // 0-based
+	ChannelPressure(u8),
+	PitchBend(u16),
+}
+
+impl ChannelMsg {
+	// Number of data bytes following the given status byte.
+	pub fn data_len(status: u8) -> usize {
+		match status & 0xF0 {
+			0xC0 | 0xD0 => 1,
+			_ => 2,
+		}
+	}
+
+	// Decodes a channel message from a status byte (0x80-0xEF) and its data
+	// bytes.
+	pub fn decode(status: u8, data: &[u8]) -> ChannelMsg {
+		let d0 = data[0] & 0x7F;
+		let d1 = data.get(1).cloned().unwrap_or(0) & 0x7F;
+		match status & 0xF0 {
+			0x80 => ChannelMsg::NoteOff { key: d0, vel: d1 },
+			// Note On with velocity 0 is kept as is, since rewriting it would
+			// change the bytes of converted files.
+			0x90 => ChannelMsg::NoteOn { key: d0, vel: d1 },
+			0xA0 => ChannelMsg::KeyPressure { key: d0, pressure: d1 },
+			0xB0 => ChannelMsg::Control { controller: d0, value: d1 },
+			0xC0 => ChannelMsg::Program(d0),
+			0xD0 => ChannelMsg::ChannelPressure(d0),
+			_ => ChannelMsg::PitchBend((d0 as u16) | ((d1 as u16) << 7)),
+		}
+	}
+
+	// Returns the status byte and data bytes for the given 0-based channel.
+	pub fn encode(&self, channel: u8) -> (u8, Vec<u8>) {
+		let channel = channel & 0x0F;
+		match *self {
+			ChannelMsg::NoteOff { key, vel } => (0x80 | channel, vec![key, vel]),
+			ChannelMsg::NoteOn { key, vel } => (0x90 | channel, vec![key, vel]),
+			ChannelMsg::KeyPressure { key, pressure } => (0xA0 | channel, vec![key, pressure]),
+			ChannelMsg::Control { controller, value } => {
+				(0xB0 | channel, vec![controller, value])
+			}
+			ChannelMsg::Program(prg) => (0xC0 | channel, vec![prg]),
+			ChannelMsg::ChannelPressure(p) => (0xD0 | channel, vec![p]),
+			ChannelMsg::PitchBend(v) => {
+				(0xE0 | channel, vec![(v & 0x7F) as u8, ((v >> 7) & 0x7F) as u8])
+			}
+		}
+	}
+}
+
+// Resets that restore the default bank and program on all channels.
+pub fn is_reset(sysex: &[u8]) -> bool {
+	// Everything after F0, excluding the device ID where applicable.
+	let body = sysex.strip_prefix(&[0xF0]).unwrap_or(sysex);
+	match *body {
+		// GM System On
+		[0x7E, _, 0x09, 0x01, ..] => true,
+		// GS Reset
+		[0x41, _, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, ..] => true,
+		// XG System On
+		[0x43, dev, 0x4C, 0x00, 0x00, 0x7E, 0x00, ..] => dev & 0xF0 == 0x10,
+		_ => false,
+	}
+}
+
+// Bank and program, as selected on a channel.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Selection {
+	pub msb: u8,
+	pub lsb: u8,
+	pub prg: u8, // 1-based, like `Voice`
+}
+
+impl fmt::Display for Selection {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:03}-{:03}-{:03}", self.msb, self.lsb, self.prg)
+	}
+}
+
+impl FromStr for Selection {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Selection, String> {
+		let mut it = s.trim().split('-').map(|n| n.parse::<u8>().ok());
+		match (it.next(), it.next(), it.next(), it.next()) {
+			(Some(Some(msb)), Some(Some(lsb)), Some(Some(prg)), None)
+				if msb < 128 && lsb < 128 && (1..=128).contains(&prg) =>
+			{
+				Ok(Selection { msb, lsb, prg })
+			}
+			_ => Err(format!("invalid address \"{}\", expected MMM-LLL-PPP", s.trim())),
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+pub struct ChannelState {
+	// Bank select values, which only take effect with the next Program
+	// Change.
+	pub msb: u8,
+	pub lsb: u8,
+	// The voice currently playing on the channel.
+	pub selection: Selection,
+	// Whether `selection` was explicitly selected by a Program Change.
+	pub explicit: bool,
+	// Whether a note has been played with the current selection.
+	pub sounded: bool,
+}
+
+impl ChannelState {
+	fn new(channel: u8) -> ChannelState {
+		let msb = if channel == DRUM_CHANNEL { DRUM_MSB } else { 0 };
+		ChannelState {
+			msb,
+			lsb: 0,
+			selection: Selection { msb, lsb: 0, prg: 1 },
+			explicit: false,
+			sounded: false,
+		}
+	}
+}
+
+// What a channel message did to the voice of its channel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Change {
+	// Program Change.
+	Selected(Selection),
+	// First note played with the power-on or reset default voice.
+	Defaulted(Selection),
+}
+
+impl Change {
+	pub fn selection(&self) -> Selection {
+		match *self {
+			Change::Selected(sel) | Change::Defaulted(sel) => sel,
+		}
+	}
+}
+
+pub struct Channels(pub [ChannelState; 16]);
+
+impl Default for Channels {
+	fn default() -> Channels {
+		let mut ret = [ChannelState::new(0); 16];
+		for (i, ch) in ret.iter_mut().enumerate() {
+			*ch = ChannelState::new(i as u8);
+		}
+		Channels(ret)
+	}
+}
+
+impl Channels {
+	pub fn reset(&mut self) {
+		*self = Default::default();
+	}
+
+	pub fn update(&mut self, channel: u8, msg: &ChannelMsg) -> Option<Change> {
+		let ch = &mut self.0[(channel & 0x0F) as usize];
+		match *msg {
+			ChannelMsg::Control { controller: CC_BANK_MSB, value } => ch.msb = value,
+			ChannelMsg::Control { controller: CC_BANK_LSB, value } => ch.lsb = value,
+			ChannelMsg::Program(prg) => {
+				ch.selection = Selection { msb: ch.msb, lsb: ch.lsb, prg: prg + 1 };
+				ch.explicit = true;
+				ch.sounded = false;
+				return Some(Change::Selected(ch.selection));
+			}
+			ChannelMsg::NoteOn { vel, .. } if vel > 0 && !ch.sounded => {
+				ch.sounded = true;
+				if !ch.explicit {
+					return Some(Change::Defaulted(ch.selection));
+				}
+			}
+			_ => {}
+		}
+		None
+	}
+}
+
+// A message read from a raw MIDI byte stream.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Message {
+	// 0-based channel.
+	Channel(u8, ChannelMsg),
+	// Including F0 and F7.
+	SysEx(Vec<u8>),
+}
+
+// Reads messages from a raw MIDI byte stream, as sent over a cable. Handles
+// running status, and skips realtime messages wherever they occur, System
+// Common messages, stray data bytes and System Exclusive messages cut short
+// by another status byte.
+#[derive(Default)]
+pub struct Parser {
+	running: Option<u8>,
+	data: Vec<u8>,
+	sysex: Option<Vec<u8>>,
+	// Data bytes still to skip for a System Common message.
+	skip: usize,
+	// Number of bytes that were not part of a complete message.
+	pub dropped: usize,
+}
+
+impl Parser {
+	pub fn feed(&mut self, byte: u8) -> Option<Message> {
+		match byte {
+			0xF8..=0xFF => None,
+			0xF0 => {
+				self.interrupt();
+				self.sysex = Some(vec![0xF0]);
+				None
+			}
+			0xF7 => match self.sysex.take() {
+				Some(mut sysex) => {
+					sysex.push(0xF7);
+					Some(Message::SysEx(sysex))
+				}
+				None => {
+					self.dropped += 1;
+					None
+				}
+			},
+			0xF1..=0xF6 => {
+				self.interrupt();
+				self.skip = match byte {
+					0xF1 | 0xF3 => 1,
+					0xF2 => 2,
+					_ => 0,
+				};
+				None
+			}
+			0x80..=0xEF => {
+				self.interrupt();
+				self.running = Some(byte);
+				None
+			}
+			_ => {
+				if let Some(ref mut sysex) = self.sysex {
+					sysex.push(byte);
+					return None;
+				}
+				if self.skip > 0 {
+					self.skip -= 1;
+					return None;
+				}
+				let status = match self.running {
+					Some(status) => status,
+					None => {
+						self.dropped += 1;
+						return None;
+					}
+				};
+				self.data.push(byte);
+				if self.data.len() < ChannelMsg::data_len(status) {
+					return None;
+				}
+				let msg = ChannelMsg::decode(status, &self.data);
+				self.data.clear();
+				Some(Message::Channel(status & 0x0F, msg))
+			}
+		}
+	}
+
+	// Drops whatever incomplete message a status byte interrupts.
+	fn interrupt(&mut self) {
+		if let Some(sysex) = self.sysex.take() {
+			self.dropped += sysex.len();
+		}
+		self.dropped += self.data.len();
+		self.data.clear();
+		self.running = None;
+		self.skip = 0;
+	}
+}
@@ -1,29 +1,143 @@
 use std::fmt;
 
+use super::effects::*;
 use super::voices::*;
+use super::xgmap::Level;
+
+// How a model plays a bank/program selection.
+#[derive(Clone, Copy)]
+pub enum Resolution {
+	Exact(&'static Voice),
+	Fallback(&'static Voice),
+	Missing,
+}
 
 pub struct Model {
-    name: &'static str,
-	year: u16,
-	voicesets: &'static [&'static Voices]
+    pub name: &'static str,
+	pub year: Option<u16>,
+	pub voicesets: &'static [&'static Voices],
+	pub effectsets: &'static [&'static EffectTypes],
+	// The part of the XG parameter map the model implements, or None if it
+	// isn't an XG module.
+	pub xg_level: Option<Level>,
+}
+
+// The XG level of a loaded model that doesn't state one: XG Level 1 if any
+// of its voices need XG bank selects.
+pub fn guess_xg_level(voicesets: &[&Voices]) -> Option<Level> {
+	let xg = voicesets.iter()
+		.flat_map(|set| set.iter())
+		.any(|v| v.lsb != 0 || v.msb == 64 || v.msb == 126);
+	if xg { Some(Level::Xg1) } else { None }
+}
+
+impl Model {
+	// Creates a model from data that was loaded at runtime. Like its voice
+	// sets, the model lives until the program exits.
+	pub fn leak(
+		name: String,
+		year: Option<u16>,
+		voicesets: Vec<&'static Voices>,
+		effectsets: Vec<&'static EffectTypes>,
+		xg_level: Option<Level>,
+	) -> &'static Model {
+		Box::leak(Box::new(Model {
+			name: Box::leak(name.into_boxed_str()),
+			year,
+			voicesets: Box::leak(voicesets.into_boxed_slice()),
+			effectsets: Box::leak(effectsets.into_boxed_slice()),
+			xg_level,
+		}))
+	}
+
+	pub fn title(&self) -> String {
+		match self.year {
+			Some(year) => format!("Yamaha {} ({})", self.name, year),
+			None => self.name.to_string(),
+		}
+	}
+
+	pub fn voices(&self) -> impl Iterator<Item = &'static Voice> {
+		self.voicesets.iter().flat_map(|set| set.iter())
+	}
+
+	pub fn voicecount(&self) -> VoiceCounts {
+		let mut ret : VoiceCounts = Default::default();
+		for it in self.voicesets {
+			ret += it.voicecount();
+		}
+		ret
+	}
+
+	pub fn effect_types(&self) -> impl Iterator<Item = &'static EffectType> {
+		self.effectsets.iter().flat_map(|set| set.iter())
+	}
+
+	pub fn effectcount(&self) -> EffectCounts {
+		let mut ret: EffectCounts = Default::default();
+		for it in self.effectsets {
+			ret += it.count();
+		}
+		ret
+	}
+
+	pub fn find_effect(&self, block: EffectBlock, msb: u8, lsb: u8) -> Option<&'static EffectType> {
+		self.effectsets.iter().filter_map(|set| set.find(block, msb, lsb)).next()
+	}
+
+	pub fn is_xg(&self) -> bool {
+		self.xg_level.is_some()
+	}
+
+	pub fn find(&self, msb: u8, lsb: u8, prg: u8) -> Option<&'static Voice> {
+		self.voicesets.iter().filter_map(|set| set.find(msb, lsb, prg)).next()
+	}
+
+	// Resolves a selection the way XG modules do: instrument variations that
+	// the model lacks fall back to the capital tone of their program, first
+	// in the same MSB, then in the GM bank. Missing drum kits fall back to the
+	// Standard Kit, while missing sound effects stay silent.
+	pub fn resolve(&self, msb: u8, lsb: u8, prg: u8) -> Resolution {
+		if let Some(voice) = self.find(msb, lsb, prg) {
+			return Resolution::Exact(voice);
+		}
+		let fallback = match VoiceKind::of_msb(msb) {
+			VoiceKind::Instrument => self.find(msb, 0, prg).or_else(|| self.find(0, 0, prg)),
+			VoiceKind::Kit => self.find(127, 0, 1),
+			VoiceKind::Sfx => None,
+		};
+		fallback.map_or(Resolution::Missing, Resolution::Fallback)
+	}
 }
 
 impl fmt::Display for Model {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-		let mut total : VoiceCounts = Default::default();
-		try!(writeln!(f, "Yamaha {} ({}):", self.name, self.year));
+		writeln!(f, "{}:", self.title())?;
 		for it in self.voicesets {
-			total += it.voicecount();
-			try!(write!(f, "{}", it));
+			write!(f, "{}", it)?;
 		}
-		writeln!(f, "{}", total)
+		writeln!(f, "{}", self.voicecount())?;
+		if !self.effectsets.is_empty() {
+			writeln!(f, "{}", self.effectcount())?;
+		}
+		Ok(())
 	}
 }
 
 pub static MU5: Model = Model {
-	name: "MU5", year: 1994, voicesets: &[&GM, &GSDrums]
+	name: "MU5", year: Some(1994), voicesets: &[GM, GSDrums], effectsets: &[], xg_level: None
 };
 
 pub static MU80: Model = Model {
-	name: "MU80", year: 1994, voicesets: &[&GM, &GSDrums, &XGLevel1]
+	name: "MU80", year: Some(1994),
+	voicesets: &[GM, GSDrums, XGLevel1],
+	effectsets: &[XGLevel1Effects, MU80Effects],
+	xg_level: Some(Level::Xg1),
 };
+
+// All built-in models, in chronological order.
+pub static MODELS: &[&Model] = &[&MU5, &MU80];
+
+pub fn find(name: &str) -> Option<&'static Model> {
+	MODELS.iter().cloned().find(|m| m.name.eq_ignore_ascii_case(name))
+}
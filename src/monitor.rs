@@ -0,0 +1,81 @@
+/*
+ * Watches a live MIDI byte stream and reports whenever the voice of a
+ * channel changes.
+ *
+ * The stream is either raw bytes, or hex text with one message per line as
+ * printed by `amidi -d`.
+ */
+
+use super::decode;
+use super::midi::{self, Change, Channels, Message, Parser, Selection};
+use super::models::Model;
+
+pub struct Monitor {
+	pub model: &'static Model,
+	parser: Parser,
+	channels: Channels,
+	// The voice last reported for each channel.
+	shown: [Option<Selection>; 16],
+}
+
+impl Monitor {
+	pub fn new(model: &'static Model) -> Monitor {
+		Monitor {
+			model,
+			parser: Default::default(),
+			channels: Default::default(),
+			shown: [None; 16],
+		}
+	}
+
+	// Number of bytes that were not part of a complete message.
+	pub fn dropped(&self) -> usize {
+		self.parser.dropped
+	}
+
+	// Reads one byte, returning a line to print if it completed a message
+	// that changed a voice or reset all channels.
+	pub fn feed(&mut self, byte: u8) -> Option<String> {
+		match self.parser.feed(byte)? {
+			Message::Channel(channel, msg) => {
+				let (sel, default) = match self.channels.update(channel, &msg)? {
+					Change::Selected(sel) => (sel, ""),
+					Change::Defaulted(sel) => (sel, ", default"),
+				};
+				let shown = &mut self.shown[channel as usize];
+				if *shown == Some(sel) {
+					return None;
+				}
+				*shown = Some(sel);
+				Some(format!(
+					"ch {:>2}: {}{}", channel + 1, decode::voice(self.model, sel), default
+				))
+			}
+			Message::SysEx(ref sysex) if midi::is_reset(sysex) => {
+				self.channels.reset();
+				self.shown = [None; 16];
+				Some("reset: all channels back to their default voices".to_string())
+			}
+			Message::SysEx(_) => None,
+		}
+	}
+}
+
+// Whether the start of a stream looks like hex text rather than raw MIDI,
+// which begins with a status byte.
+pub fn is_text(start: &[u8]) -> bool {
+	start.iter()
+		.find(|b| !b.is_ascii_whitespace())
+		.is_some_and(|b| b.is_ascii_hexdigit())
+}
+
+// The bytes of a line of hex text, or None for other lines, like the
+// byte count that amidi prints when it stops.
+pub fn hex_line(line: &str) -> Option<Vec<u8>> {
+	line.split_whitespace()
+		.map(|word| match word.len() {
+			2 => u8::from_str_radix(word, 16).ok(),
+			_ => None,
+		})
+		.collect()
+}
@@ -0,0 +1,94 @@
+/*
+ * XG voice edits over NRPN.
+ *
+ * An NRPN is selected with CC99 (MSB) and CC98 (LSB), and set with Data
+ * Entry (CC6). Part NRPNs edit the voice of the receiving part; drum NRPNs
+ * use the note number as LSB and edit that instrument of the part's kit.
+ *
+ * All of them are part of XG Level 1, so every XG module receives them.
+ */
+
+use super::midi::ChannelMsg;
+use super::models::Model;
+use super::xgmap::Format;
+
+pub const CC_DATA_ENTRY: u8 = 6;
+pub const CC_NRPN_LSB: u8 = 98;
+pub const CC_NRPN_MSB: u8 = 99;
+
+pub struct Nrpn {
+	pub msb: u8,
+	// None for drum NRPNs, whose LSB is the note.
+	pub lsb: Option<u8>,
+	pub name: &'static str,
+	pub format: Format,
+	pub min: u8,
+	pub max: u8,
+}
+
+const fn n(msb: u8, lsb: Option<u8>, name: &'static str, format: Format) -> Nrpn {
+	Nrpn { msb, lsb, name, format, min: 0, max: 127 }
+}
+
+// Part NRPNs are offsets to the voice's own settings.
+const fn part(lsb: u8, name: &'static str) -> Nrpn {
+	n(0x01, Some(lsb), name, Format::Signed)
+}
+
+const fn drum(msb: u8, name: &'static str, format: Format) -> Nrpn {
+	n(msb, None, name, format)
+}
+
+pub static NRPNS: &[Nrpn] = &[
+	part(0x08, "Vibrato Rate"),
+	part(0x09, "Vibrato Depth"),
+	part(0x0A, "Vibrato Delay"),
+	part(0x20, "Filter Cutoff Frequency"),
+	part(0x21, "Filter Resonance"),
+	part(0x63, "EG Attack Time"),
+	part(0x64, "EG Decay Time"),
+	part(0x66, "EG Release Time"),
+	drum(0x14, "Drum Filter Cutoff Frequency", Format::Signed),
+	drum(0x15, "Drum Filter Resonance", Format::Signed),
+	drum(0x16, "Drum EG Attack Rate", Format::Signed),
+	drum(0x17, "Drum EG Decay Rate", Format::Signed),
+	drum(0x18, "Drum Pitch Coarse", Format::Semitones),
+	drum(0x19, "Drum Pitch Fine", Format::Cents),
+	drum(0x1A, "Drum Level", Format::Number),
+	drum(0x1C, "Drum Pan", Format::Pan),
+	drum(0x1D, "Drum Reverb Send", Format::Number),
+	drum(0x1E, "Drum Chorus Send", Format::Number),
+	drum(0x1F, "Drum Variation Send", Format::Number),
+];
+
+impl Nrpn {
+	// The Control Changes that set the NRPN to `value` on `model`. `note`
+	// must be given for drum NRPNs, and only for them.
+	pub fn messages(&self, model: &Model, note: Option<u8>, value: u8)
+		-> Result<Vec<ChannelMsg>, String>
+	{
+		if !model.is_xg() {
+			return Err(format!("{} does not support XG NRPNs", model.name));
+		}
+		let lsb = match (self.lsb, note) {
+			(Some(lsb), None) => lsb,
+			(None, Some(note)) if note < 128 => note,
+			(None, Some(note)) => return Err(format!("invalid note: {}", note)),
+			(None, None) => return Err(format!("{} needs a note", self.name)),
+			(Some(_), Some(_)) => return Err(format!("{} doesn't take a note", self.name)),
+		};
+		if value < self.min || value > self.max {
+			return Err(format!(
+				"{} is out of range for {} ({} to {})", value, self.name, self.min, self.max
+			));
+		}
+		Ok([(CC_NRPN_MSB, self.msb), (CC_NRPN_LSB, lsb), (CC_DATA_ENTRY, value)].iter()
+			.map(|&(controller, value)| ChannelMsg::Control { controller, value })
+			.collect())
+	}
+}
+
+// Ignores case.
+pub fn find(name: &str) -> Option<&'static Nrpn> {
+	NRPNS.iter().find(|n| n.name.eq_ignore_ascii_case(name))
+}
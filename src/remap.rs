@@ -0,0 +1,221 @@
+/*
+ * Rewrites the voice selections of an SMF for a less capable model.
+ */
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use super::midi::*;
+use super::models::{Model, Resolution};
+use super::smf::{Event, Smf, Track, TrackEvent};
+use super::voices::*;
+
+// User-provided substitutions, taking precedence over the automatic ones.
+// Every non-empty line maps one address to another, as in
+//
+// 	000-041-001 = 000-000-089  # Dream → New Age Pad
+#[derive(Default)]
+pub struct Mapping(BTreeMap<Selection, Selection>);
+
+impl Mapping {
+	pub fn parse(text: &str) -> Result<Mapping, String> {
+		let mut ret = Mapping::default();
+		for (i, line) in text.lines().enumerate() {
+			let line = line.split('#').next().unwrap().trim();
+			if line.is_empty() {
+				continue;
+			}
+			let pair = line.split_once('=')
+				.and_then(|(from, to)| Some((from.parse().ok()?, to.parse().ok()?)))
+				.ok_or_else(|| format!("line {}: expected MMM-LLL-PPP = MMM-LLL-PPP", i + 1))?;
+			ret.0.insert(pair.0, pair.1);
+		}
+		Ok(ret)
+	}
+
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Mapping, String> {
+		let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+		Mapping::parse(&text)
+	}
+
+	// Fails if a substitution selects a voice that `target` doesn't have,
+	// since it could never be used.
+	pub fn check(&self, target: &Model) -> Result<(), String> {
+		match self.0.iter().find(|(_, to)| target.find(to.msb, to.lsb, to.prg).is_none()) {
+			Some((from, to)) => Err(format!(
+				"mapping {} = {}: {} has no voice {}", from, to, target.name, to
+			)),
+			None => Ok(()),
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+	Mapped,
+	CapitalTone,
+	// Another kit, for kits that the target doesn't have.
+	DrumKit,
+	Category,
+	// No substitute found, the selection was left as is.
+	Missing,
+}
+
+pub struct Substitution {
+	pub track: usize,
+	pub tick: u64,
+	pub channel: u8,
+	pub from: Selection,
+	pub from_voice: Option<&'static Voice>,
+	pub to: Selection,
+	pub to_voice: Option<&'static Voice>,
+	pub reason: Reason,
+}
+
+impl fmt::Display for Substitution {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let reason = match self.reason {
+			Reason::Mapped => "mapping",
+			Reason::CapitalTone => "capital tone",
+			Reason::DrumKit => "drum kit",
+			Reason::Category => "same category",
+			Reason::Missing => "no substitute",
+		};
+		write!(f,
+			"track {:>2}, tick {:>6}, channel {:>2}: {} {} -> {} {} ({})",
+			self.track + 1, self.tick, self.channel + 1,
+			self.from, self.from_voice.map_or("?", |v| v.name()),
+			self.to, self.to_voice.map_or("?", |v| v.name()),
+			reason
+		)
+	}
+}
+
+fn address(voice: &Voice) -> Selection {
+	Selection { msb: voice.msb, lsb: voice.lsb, prg: voice.prg }
+}
+
+pub struct Remapper<'a> {
+	pub source: &'static Model,
+	pub target: &'static Model,
+	pub mapping: &'a Mapping,
+}
+
+impl<'a> Remapper<'a> {
+	// The capital tone of the first program in the same GM category. Sound
+	// effects are matched against GM's "Sound Effects" category.
+	fn same_category(&self, voice: &Voice) -> Option<&'static Voice> {
+		let category = match voice.kind() {
+			VoiceKind::Instrument => gm_category(voice.prg),
+			VoiceKind::Sfx => GM_CATEGORIES[15],
+			VoiceKind::Kit => return None,
+		};
+		self.target.voices()
+			.filter(|v| v.kind() == VoiceKind::Instrument && gm_category(v.prg) == category)
+			.min_by_key(|v| (v.lsb != 0, v.prg, v.msb, v.lsb))
+	}
+
+	// Returns the selection to use on the target model instead of `sel`,
+	// or `None` if `sel` can stay as it is.
+	pub fn substitute(&self, sel: Selection) -> Option<(Selection, Reason)> {
+		// Mappings to voices the target lacks are rejected by `Mapping::check`.
+		if let Some(&to) = self.mapping.0.get(&sel) {
+			return Some((to, Reason::Mapped));
+		}
+		// The voice the song was actually heard with.
+		let intended = match self.source.resolve(sel.msb, sel.lsb, sel.prg) {
+			Resolution::Exact(v) | Resolution::Fallback(v) => v,
+			Resolution::Missing => {
+				return match self.target.find(sel.msb, sel.lsb, sel.prg) {
+					Some(_) => None,
+					None => Some((sel, Reason::Missing)),
+				};
+			}
+		};
+		match self.target.resolve(intended.msb, intended.lsb, intended.prg) {
+			Resolution::Exact(v) if address(v) == sel => None,
+			Resolution::Exact(v) | Resolution::Fallback(v) if intended.kind() == VoiceKind::Kit => {
+				Some((address(v), Reason::DrumKit))
+			}
+			Resolution::Exact(v) | Resolution::Fallback(v) => {
+				Some((address(v), Reason::CapitalTone))
+			}
+			Resolution::Missing => match self.same_category(intended) {
+				Some(v) => Some((address(v), Reason::Category)),
+				None => Some((sel, Reason::Missing)),
+			},
+		}
+	}
+
+	// Rewrites all Program Changes that need a substitution, inserting bank
+	// selects wherever the bank of the output differs from the required one.
+	// Bank selects of the original are kept.
+	pub fn remap(&self, smf: &Smf) -> (Smf, Vec<Substitution>) {
+		let mut tracks: Vec<Vec<TrackEvent>> = vec![Vec::new(); smf.tracks.len()];
+		let mut log = Vec::new();
+		for sequence in smf.sequences() {
+			let mut input: Channels = Default::default();
+			let mut output: Channels = Default::default();
+			for (track, e) in sequence {
+				let out = &mut tracks[track];
+				let push = |out: &mut Vec<TrackEvent>, event| {
+					out.push(TrackEvent { tick: e.tick, event })
+				};
+				match e.event {
+					Event::Channel { channel, msg: ref msg @ ChannelMsg::Program(_) } => {
+						// Program Changes always select a voice.
+						let sel = input.update(channel, msg).unwrap().selection();
+						let to = match self.substitute(sel) {
+							Some((to, reason)) => {
+								log.push(Substitution {
+									track,
+									tick: e.tick,
+									channel,
+									from: sel,
+									from_voice: self.source.find(sel.msb, sel.lsb, sel.prg),
+									to,
+									to_voice: self.target.find(to.msb, to.lsb, to.prg),
+									reason,
+								});
+								to
+							}
+							None => sel,
+						};
+						let state = output.0[channel as usize];
+						for &(cc, current, value) in &[
+							(CC_BANK_MSB, state.msb, to.msb), (CC_BANK_LSB, state.lsb, to.lsb)
+						] {
+							if current != value {
+								let msg = ChannelMsg::Control { controller: cc, value };
+								output.update(channel, &msg);
+								push(out, Event::Channel { channel, msg });
+							}
+						}
+						let msg = ChannelMsg::Program(to.prg - 1);
+						output.update(channel, &msg);
+						push(out, Event::Channel { channel, msg });
+					}
+					Event::Channel { channel, ref msg } => {
+						input.update(channel, msg);
+						output.update(channel, msg);
+						out.push(e.clone());
+					}
+					Event::SysEx(ref data) if is_reset(data) => {
+						input.reset();
+						output.reset();
+						out.push(e.clone());
+					}
+					_ => out.push(e.clone()),
+				}
+			}
+		}
+		let smf = Smf {
+			format: smf.format,
+			division: smf.division,
+			tracks: tracks.into_iter().map(|events| Track { events }).collect(),
+		};
+		(smf, log)
+	}
+}
@@ -0,0 +1,560 @@
+/*
+ * Standard MIDI File (format 0, 1 and 2) reader and writer.
+ */
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::midi::*;
+
+#[derive(Debug)]
+pub enum Error {
+	Io(io::Error),
+	NotSmf,
+	Truncated { offset: usize },
+	// Data byte without any preceding status byte.
+	NoRunningStatus { offset: usize },
+	// System Common or Real-Time status byte, which SMF tracks can't contain.
+	BadStatus { status: u8, offset: usize },
+	// Delta time or length beyond the 28 bits of a variable-length quantity.
+	TooLarge { value: u64 },
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Error::Io(ref e) => write!(f, "{}", e),
+			Error::NotSmf => write!(f, "not a Standard MIDI File"),
+			Error::Truncated { offset } => write!(f, "file is truncated at byte {}", offset),
+			Error::NoRunningStatus { offset } => {
+				write!(f, "data byte without running status at byte {}", offset)
+			}
+			Error::BadStatus { status, offset } => {
+				write!(f, "status byte {:02X} at byte {} is not allowed in a track", status, offset)
+			}
+			Error::TooLarge { value } => {
+				write!(f, "{} doesn't fit into a variable-length quantity", value)
+			}
+		}
+	}
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+	fn from(e: io::Error) -> Error {
+		Error::Io(e)
+	}
+}
+
+pub const META_TRACK_NAME: u8 = 0x03;
+pub const META_MARKER: u8 = 0x06;
+pub const META_END_OF_TRACK: u8 = 0x2F;
+pub const META_TEMPO: u8 = 0x51;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Event {
+	Channel { channel: u8, msg: ChannelMsg },
+	// Complete message, including the leading F0.
+	SysEx(Vec<u8>),
+	// F7 escape, without the F7.
+	Escape(Vec<u8>),
+	Meta { kind: u8, data: Vec<u8> },
+}
+
+#[derive(Clone, Debug)]
+pub struct TrackEvent {
+	// Absolute time since the start of the track.
+	pub tick: u64,
+	pub event: Event,
+}
+
+#[derive(Clone, Default)]
+pub struct Track {
+	pub events: Vec<TrackEvent>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Division {
+	TicksPerQuarter(u16),
+	// Frames per second and ticks per frame.
+	Smpte(u8, u8),
+}
+
+// Frames per second of the SMPTE formats, stored negated in the high byte
+// of the division. 29 means 29.97 drop-frame.
+const SMPTE_FORMATS: [u8; 4] = [24, 25, 29, 30];
+
+#[derive(Clone)]
+pub struct Smf {
+	pub format: u16,
+	pub division: Division,
+	pub tracks: Vec<Track>,
+}
+
+// Keeps byte offsets relative to the start of the file, for error messages.
+struct Reader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+		let end = self.pos.checked_add(len)
+			.filter(|&end| end <= self.data.len())
+			.ok_or(Error::Truncated { offset: self.data.len() })?;
+		let ret = &self.data[self.pos..end];
+		self.pos = end;
+		Ok(ret)
+	}
+
+	fn u8(&mut self) -> Result<u8, Error> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn u16(&mut self) -> Result<u16, Error> {
+		let b = self.bytes(2)?;
+		Ok(((b[0] as u16) << 8) | b[1] as u16)
+	}
+
+	fn u32(&mut self) -> Result<u32, Error> {
+		let b = self.bytes(4)?;
+		Ok(b.iter().fold(0, |acc, &b| (acc << 8) | b as u32))
+	}
+
+	// Variable-length quantity, at most 4 bytes.
+	fn vlq(&mut self) -> Result<u32, Error> {
+		let mut ret = 0;
+		for _ in 0..4 {
+			let b = self.u8()?;
+			ret = (ret << 7) | (b & 0x7F) as u32;
+			if b & 0x80 == 0 {
+				break;
+			}
+		}
+		Ok(ret)
+	}
+}
+
+fn read_track(r: &mut Reader) -> Result<Track, Error> {
+	let mut ret = Track::default();
+	let mut tick = 0;
+	let mut running_status = None;
+	while r.pos < r.data.len() {
+		tick += r.vlq()? as u64;
+		let offset = r.pos;
+		let status = r.u8()?;
+		let event = match status {
+			0xF0 => {
+				let len = r.vlq()? as usize;
+				let mut msg = vec![0xF0];
+				msg.extend_from_slice(r.bytes(len)?);
+				Event::SysEx(msg)
+			}
+			0xF7 => {
+				let len = r.vlq()? as usize;
+				Event::Escape(r.bytes(len)?.to_vec())
+			}
+			0xFF => {
+				let kind = r.u8()?;
+				let len = r.vlq()? as usize;
+				let data = r.bytes(len)?.to_vec();
+				if kind == META_END_OF_TRACK {
+					ret.events.push(TrackEvent { tick, event: Event::Meta { kind, data } });
+					break;
+				}
+				Event::Meta { kind, data }
+			}
+			0xF1..=0xFE => return Err(Error::BadStatus { status, offset }),
+			// Strictly speaking, SysEx and meta events cancel running status.
+			// Real-world files don't always agree, so we keep it.
+			status => {
+				let (status, data) = if status & 0x80 != 0 {
+					running_status = Some(status);
+					let len = ChannelMsg::data_len(status);
+					(status, r.bytes(len)?)
+				} else {
+					let status = running_status.ok_or(Error::NoRunningStatus { offset })?;
+					r.pos -= 1;
+					(status, r.bytes(ChannelMsg::data_len(status))?)
+				};
+				Event::Channel { channel: status & 0x0F, msg: ChannelMsg::decode(status, data) }
+			}
+		};
+		ret.events.push(TrackEvent { tick, event });
+	}
+	Ok(ret)
+}
+
+const VLQ_MAX: u64 = 0x0FFF_FFFF;
+
+fn write_vlq(out: &mut Vec<u8>, value: u64) -> Result<(), Error> {
+	if value > VLQ_MAX {
+		return Err(Error::TooLarge { value });
+	}
+	let mut shift = 21;
+	while shift > 0 && (value >> shift) == 0 {
+		shift -= 7;
+	}
+	while shift > 0 {
+		out.push(((value >> shift) & 0x7F) as u8 | 0x80);
+		shift -= 7;
+	}
+	out.push((value & 0x7F) as u8);
+	Ok(())
+}
+
+// Uses running status for channel messages, and appends an End of Track
+// event if the track doesn't end with one.
+fn write_track(track: &Track) -> Result<Vec<u8>, Error> {
+	let mut ret = Vec::new();
+	let mut tick = 0;
+	let mut running_status = None;
+	let mut ended = false;
+	for e in &track.events {
+		write_vlq(&mut ret, e.tick.saturating_sub(tick))?;
+		tick = tick.max(e.tick);
+		match e.event {
+			Event::Channel { channel, ref msg } => {
+				let (status, data) = msg.encode(channel);
+				if running_status != Some(status) {
+					ret.push(status);
+					running_status = Some(status);
+				}
+				ret.extend_from_slice(&data);
+			}
+			Event::SysEx(ref data) => {
+				ret.push(0xF0);
+				write_vlq(&mut ret, (data.len() - 1) as u64)?;
+				ret.extend_from_slice(&data[1..]);
+				running_status = None;
+			}
+			Event::Escape(ref data) => {
+				ret.push(0xF7);
+				write_vlq(&mut ret, data.len() as u64)?;
+				ret.extend_from_slice(data);
+				running_status = None;
+			}
+			Event::Meta { kind, ref data } => {
+				ret.extend_from_slice(&[0xFF, kind]);
+				write_vlq(&mut ret, data.len() as u64)?;
+				ret.extend_from_slice(data);
+				running_status = None;
+				if kind == META_END_OF_TRACK {
+					ended = true;
+					break;
+				}
+			}
+		}
+	}
+	if !ended {
+		ret.extend_from_slice(&[0x00, 0xFF, META_END_OF_TRACK, 0x00]);
+	}
+	Ok(ret)
+}
+
+impl Smf {
+	pub fn parse(data: &[u8]) -> Result<Smf, Error> {
+		let mut r = Reader { data, pos: 0 };
+
+		// RIFF MIDI files (.rmi) wrap a regular SMF in their `data` chunk.
+		if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"RMID") {
+			r.pos = 12;
+			while r.pos < data.len() {
+				let id = r.bytes(4)?;
+				let len = (r.u32()?).swap_bytes() as usize;
+				if id == b"data" {
+					return Smf::parse(r.bytes(len)?);
+				}
+				r.bytes(len + (len & 1))?;
+			}
+			return Err(Error::NotSmf);
+		}
+
+		if r.bytes(4).ok() != Some(b"MThd") {
+			return Err(Error::NotSmf);
+		}
+		let header_len = r.u32()? as usize;
+		let header_start = r.pos;
+		r.bytes(header_len)?;
+		let mut header = Reader { data: &data[..r.pos], pos: header_start };
+		let format = header.u16()?;
+		let ntrks = header.u16()?;
+		let division = match header.u16()? {
+			d if d & 0x8000 != 0 => {
+				let fps = (0x100 - (d >> 8)) as u8;
+				if !SMPTE_FORMATS.contains(&fps) {
+					return Err(Error::NotSmf);
+				}
+				Division::Smpte(fps, d as u8)
+			}
+			d => Division::TicksPerQuarter(d),
+		};
+
+		let mut tracks = Vec::new();
+		while tracks.len() < ntrks as usize && r.pos < data.len() {
+			let id = r.bytes(4)?;
+			let len = r.u32()? as usize;
+			// Truncated files are common enough to try reading what's there.
+			let len = len.min(data.len() - r.pos);
+			let start = r.pos;
+			r.bytes(len)?;
+			if id == b"MTrk" {
+				tracks.push(read_track(&mut Reader { data: &data[..r.pos], pos: start })?);
+			}
+		}
+		Ok(Smf { format, division, tracks })
+	}
+
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Smf, Error> {
+		Smf::parse(&fs::read(path)?)
+	}
+
+	pub fn write(&self) -> Result<Vec<u8>, Error> {
+		let mut ret = b"MThd".to_vec();
+		ret.extend_from_slice(&6u32.to_be_bytes());
+		ret.extend_from_slice(&self.format.to_be_bytes());
+		ret.extend_from_slice(&(self.tracks.len() as u16).to_be_bytes());
+		let division = match self.division {
+			Division::TicksPerQuarter(tpq) => tpq,
+			Division::Smpte(fps, tpf) if SMPTE_FORMATS.contains(&fps) => {
+				(0x100 - fps as u16) << 8 | tpf as u16
+			}
+			Division::Smpte(..) => return Err(Error::NotSmf),
+		};
+		ret.extend_from_slice(&division.to_be_bytes());
+		for track in &self.tracks {
+			let data = write_track(track)?;
+			ret.extend_from_slice(b"MTrk");
+			ret.extend_from_slice(&(data.len() as u32).to_be_bytes());
+			ret.extend_from_slice(&data);
+		}
+		Ok(ret)
+	}
+
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+		Ok(fs::write(path, self.write()?)?)
+	}
+
+	// Groups the tracks into independently playing sequences of
+	// (track index, event) tuples in playback order. Format 2 files consist
+	// of one sequence per track, all others of a single merged one.
+	pub fn sequences(&self) -> Vec<Vec<(usize, &TrackEvent)>> {
+		let all = |tracks: &mut dyn Iterator<Item = usize>| {
+			let mut ret: Vec<(usize, &TrackEvent)> = tracks
+				.flat_map(|i| self.tracks[i].events.iter().map(move |e| (i, e)))
+				.collect();
+			// Stable, so that simultaneous events keep their track order.
+			ret.sort_by_key(|&(_, e)| e.tick);
+			ret
+		};
+		if self.format == 2 {
+			(0..self.tracks.len()).map(|i| all(&mut (i..i + 1))).collect()
+		} else {
+			vec![all(&mut (0..self.tracks.len()))]
+		}
+	}
+}
+
+// Converts ticks to seconds for a single sequence.
+pub struct TempoMap {
+	division: Division,
+	// (tick, seconds at this tick, microseconds per quarter note from here)
+	changes: Vec<(u64, f64, u32)>,
+}
+
+impl TempoMap {
+	pub fn new(division: Division, sequence: &[(usize, &TrackEvent)]) -> TempoMap {
+		let mut ret = TempoMap { division, changes: vec![(0, 0.0, 500_000)] };
+		for &(_, e) in sequence {
+			if let Event::Meta { kind: META_TEMPO, ref data } = e.event {
+				if data.len() == 3 {
+					let tempo = data.iter().fold(0, |acc, &b| (acc << 8) | b as u32);
+					let seconds = ret.seconds(e.tick);
+					ret.changes.push((e.tick, seconds, tempo));
+				}
+			}
+		}
+		ret
+	}
+
+	pub fn seconds(&self, tick: u64) -> f64 {
+		match self.division {
+			Division::Smpte(fps, tpf) => {
+				// 29 means 29.97 drop-frame.
+				let fps = if fps == 29 { 29.97 } else { fps as f64 };
+				tick as f64 / (fps * tpf.max(1) as f64)
+			}
+			Division::TicksPerQuarter(tpq) => {
+				let &(start, seconds, tempo) = self.changes.iter()
+					.rev()
+					.find(|c| c.0 <= tick)
+					.unwrap();
+				seconds + ((tick - start) as f64 * tempo as f64) / (tpq.max(1) as f64 * 1e6)
+			}
+		}
+	}
+}
+
+// Formats seconds as m:ss.mmm.
+pub struct Timestamp(pub f64);
+
+impl fmt::Display for Timestamp {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let ms = (self.0 * 1000.0).round() as u64;
+		write!(f, "{}:{:02}.{:03}", ms / 60_000, (ms / 1000) % 60, ms % 1000)
+	}
+}
+
+// A voice change within a sequence.
+pub struct VoiceChange {
+	pub track: usize,
+	pub tick: u64,
+	pub channel: u8,
+	pub change: Change,
+}
+
+// Runs the channel state machine over a sequence.
+pub fn voice_changes(sequence: &[(usize, &TrackEvent)]) -> Vec<VoiceChange> {
+	let mut channels: Channels = Default::default();
+	let mut ret = Vec::new();
+	for &(track, e) in sequence {
+		match e.event {
+			Event::Channel { channel, ref msg } => {
+				if let Some(change) = channels.update(channel, msg) {
+					ret.push(VoiceChange { track, tick: e.tick, channel, change });
+				}
+			}
+			Event::SysEx(ref data) if is_reset(data) => channels.reset(),
+			_ => {}
+		}
+	}
+	ret
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn file(division: u16, track: &[u8]) -> Vec<u8> {
+		let mut ret = b"MThd".to_vec();
+		ret.extend_from_slice(&6u32.to_be_bytes());
+		ret.extend_from_slice(&[0, 0, 0, 1]);
+		ret.extend_from_slice(&division.to_be_bytes());
+		ret.extend_from_slice(b"MTrk");
+		ret.extend_from_slice(&(track.len() as u32).to_be_bytes());
+		ret.extend_from_slice(track);
+		ret
+	}
+
+	fn events(smf: &Smf) -> Vec<(u64, Event)> {
+		smf.tracks[0].events.iter().map(|e| (e.tick, e.event.clone())).collect()
+	}
+
+	#[test]
+	fn vlq_edge_values() {
+		let cases: &[(u64, &[u8])] = &[
+			(0, &[0x00]),
+			(0x7F, &[0x7F]),
+			(0x80, &[0x81, 0x00]),
+			(0x3FFF, &[0xFF, 0x7F]),
+			(0x0FFF_FFFF, &[0xFF, 0xFF, 0xFF, 0x7F]),
+		];
+		for &(value, bytes) in cases {
+			let mut out = Vec::new();
+			write_vlq(&mut out, value).unwrap();
+			assert_eq!(out, bytes, "writing {:#X}", value);
+			assert_eq!(Reader { data: bytes, pos: 0 }.vlq().unwrap() as u64, value);
+		}
+		assert!(matches!(
+			write_vlq(&mut Vec::new(), 0x1000_0000), Err(Error::TooLarge { value: 0x1000_0000 })
+		));
+	}
+
+	#[test]
+	fn running_status() {
+		let track = [
+			0x00, 0x90, 0x3C, 0x64,
+			0x10, 0x3E, 0x64,
+			0x10, 0x80, 0x3C, 0x00,
+			0x00, 0xFF, 0x2F, 0x00,
+		];
+		let smf = Smf::parse(&file(96, &track)).unwrap();
+		assert_eq!(events(&smf)[..3], [
+			(0, Event::Channel { channel: 0, msg: ChannelMsg::NoteOn { key: 0x3C, vel: 0x64 } }),
+			(16, Event::Channel { channel: 0, msg: ChannelMsg::NoteOn { key: 0x3E, vel: 0x64 } }),
+			(32, Event::Channel { channel: 0, msg: ChannelMsg::NoteOff { key: 0x3C, vel: 0 } }),
+		]);
+		// Written back with running status, byte for byte.
+		assert_eq!(write_track(&smf.tracks[0]).unwrap(), track);
+	}
+
+	#[test]
+	fn data_byte_without_status() {
+		match Smf::parse(&file(96, &[0x00, 0x3C, 0x64])) {
+			Err(Error::NoRunningStatus { offset }) => assert_eq!(offset, 23),
+			_ => panic!("expected NoRunningStatus"),
+		}
+	}
+
+	#[test]
+	fn system_status_in_track() {
+		// Song Select and Active Sensing, neither of which an SMF can carry.
+		for &status in &[0xF2, 0xFE] {
+			match Smf::parse(&file(96, &[0x00, 0x90, 0x3C, 0x64, 0x00, status, 0x01, 0x02])) {
+				Err(Error::BadStatus { status: s, offset }) => {
+					assert_eq!((s, offset), (status, 27));
+				}
+				_ => panic!("expected BadStatus for {:02X}", status),
+			}
+		}
+	}
+
+	#[test]
+	fn delta_too_large() {
+		let event = |tick| TrackEvent {
+			tick,
+			event: Event::Meta { kind: META_MARKER, data: Vec::new() },
+		};
+		let smf = Smf {
+			format: 0,
+			division: Division::TicksPerQuarter(96),
+			tracks: vec![Track { events: vec![event(0), event(VLQ_MAX + 1)] }],
+		};
+		assert!(matches!(smf.write(), Err(Error::TooLarge { value }) if value == VLQ_MAX + 1));
+	}
+
+	#[test]
+	fn smpte_division() {
+		let smf = Smf::parse(&file(0xE728, &[0x00, 0xFF, 0x2F, 0x00])).unwrap();
+		assert_eq!(smf.division, Division::Smpte(25, 40));
+		assert_eq!(&smf.write().unwrap()[12..14], &[0xE7, 0x28]);
+		// -128 frames per second isn't an SMPTE format.
+		assert!(matches!(Smf::parse(&file(0x8004, &[])), Err(Error::NotSmf)));
+		let bad = Smf { format: 0, division: Division::Smpte(128, 4), tracks: Vec::new() };
+		assert!(matches!(bad.write(), Err(Error::NotSmf)));
+	}
+
+	#[test]
+	fn round_trip() {
+		let track = [
+			0x00, 0xF0, 0x05, 0x7E, 0x7F, 0x09, 0x01, 0xF7,
+			0x00, 0xFF, 0x03, 0x04, b'T', b'e', b's', b't',
+			0x00, 0xB0, 0x00, 0x00,
+			0x00, 0x20, 0x08,
+			0x00, 0xC0, 0x04,
+			0x81, 0x00, 0xE1, 0x00, 0x40,
+			0x83, 0x60, 0xFF, 0x2F, 0x00,
+		];
+		let first = Smf::parse(&file(480, &track)).unwrap();
+		let written = first.write().unwrap();
+		let second = Smf::parse(&written).unwrap();
+		assert_eq!(second.format, first.format);
+		assert_eq!(second.division, Division::TicksPerQuarter(480));
+		assert_eq!(events(&second), events(&first));
+		assert_eq!(second.write().unwrap(), written);
+	}
+}
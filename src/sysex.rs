@@ -0,0 +1,265 @@
+/*
+ * Builders for GM, GS and XG System Exclusive messages.
+ *
+ * All messages include the leading F0 and trailing F7, like `Event::SysEx`.
+ * Device numbers are 0-15, as on the module's panel minus 1.
+ */
+
+use std::fmt;
+
+use super::voices::{Voice, VoiceKind};
+
+const YAMAHA: u8 = 0x43;
+const XG_MODEL: u8 = 0x4C;
+const ROLAND: u8 = 0x41;
+const GS_MODEL: u8 = 0x42;
+
+// Roland DT1 (Data Set 1) command.
+const DT1: u8 = 0x12;
+
+// Bytes as "F0 43 10 4C ...".
+pub struct Hex<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Display for Hex<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for (i, b) in self.0.iter().enumerate() {
+			if i > 0 {
+				f.write_str(" ")?;
+			}
+			write!(f, "{:02X}", b)?;
+		}
+		Ok(())
+	}
+}
+
+// Address of an XG parameter. The high byte selects the block (system,
+// effect, multi part, drum setup), the middle byte usually the part or
+// note, and the low byte the parameter.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Address {
+	pub high: u8,
+	pub mid: u8,
+	pub low: u8,
+}
+
+impl Address {
+	pub fn system(param: u8) -> Address {
+		Address { high: 0x00, mid: 0x00, low: param }
+	}
+
+	// `part` is 0-based.
+	pub fn multi_part(part: u8, param: MultiPart) -> Address {
+		Address { high: 0x08, mid: part, low: param as u8 }
+	}
+}
+
+impl fmt::Display for Address {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:02X} {:02X} {:02X}", self.high, self.mid, self.low)
+	}
+}
+
+// XG System parameters.
+pub const XG_SYSTEM_ON: u8 = 0x7E;
+
+// XG Multi Part parameters needed to select a voice, and to tell which
+// channel it plays on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MultiPart {
+	BankMsb = 0x01,
+	BankLsb = 0x02,
+	Program = 0x03,
+	RcvChannel = 0x04,
+	PartMode = 0x07,
+}
+
+// Values of `MultiPart::PartMode`.
+pub const PART_MODE_NORMAL: u8 = 0;
+pub const PART_MODE_DRUM: u8 = 1;
+
+// Yamaha and Roland use the same checksum: the value that makes the low 7
+// bits of the sum of all checksummed bytes zero.
+pub fn checksum(bytes: &[u8]) -> u8 {
+	let sum: u32 = bytes.iter().map(|&b| b as u32).sum();
+	((128 - sum % 128) % 128) as u8
+}
+
+// Universal Identity Request, answered by the module with `device`, or by
+// all modules if `device` is 0x7F.
+pub fn identity_request(device: u8) -> Vec<u8> {
+	vec![0xF0, 0x7E, device, 0x06, 0x01, 0xF7]
+}
+
+pub fn gm_system_on() -> Vec<u8> {
+	vec![0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]
+}
+
+// Roland DT1 message setting `data` at the 3-byte GS `address`.
+pub fn gs_data_set(device: u8, address: [u8; 3], data: &[u8]) -> Vec<u8> {
+	let mut body = address.to_vec();
+	body.extend_from_slice(data);
+	let mut ret = vec![0xF0, ROLAND, 0x10 | (device & 0x0F), GS_MODEL, DT1];
+	ret.extend_from_slice(&body);
+	ret.push(checksum(&body));
+	ret.push(0xF7);
+	ret
+}
+
+pub fn gs_reset(device: u8) -> Vec<u8> {
+	gs_data_set(device, [0x40, 0x00, 0x7F], &[0x00])
+}
+
+// XG Parameter Change. Most parameters take a single byte, some two or
+// four.
+pub fn xg_parameter_change(device: u8, address: Address, data: &[u8]) -> Vec<u8> {
+	let mut ret = vec![0xF0, YAMAHA, 0x10 | (device & 0x0F), XG_MODEL];
+	ret.extend_from_slice(&[address.high, address.mid, address.low]);
+	ret.extend_from_slice(data);
+	ret.push(0xF7);
+	ret
+}
+
+pub fn xg_system_on(device: u8) -> Vec<u8> {
+	xg_parameter_change(device, Address::system(XG_SYSTEM_ON), &[0x00])
+}
+
+// XG Bulk Dump of `data` starting at `address`, which should be the start
+// of a parameter block. The checksum covers the byte count, the address and
+// the data.
+pub fn xg_bulk_dump(device: u8, address: Address, data: &[u8]) -> Vec<u8> {
+	let count = data.len();
+	let mut body = vec![((count >> 7) & 0x7F) as u8, (count & 0x7F) as u8];
+	body.extend_from_slice(&[address.high, address.mid, address.low]);
+	body.extend_from_slice(data);
+	let mut ret = vec![0xF0, YAMAHA, device & 0x0F, XG_MODEL];
+	ret.extend_from_slice(&body);
+	ret.push(checksum(&body));
+	ret.push(0xF7);
+	ret
+}
+
+// Parameter changes that select `voice` on the 0-based `part`, switching
+// the part to drum mode for kits.
+pub fn xg_voice_setup(device: u8, part: u8, voice: &Voice) -> Vec<Vec<u8>> {
+	let mode = match voice.kind() {
+		VoiceKind::Kit => PART_MODE_DRUM,
+		_ => PART_MODE_NORMAL,
+	};
+	[
+		(MultiPart::PartMode, mode),
+		(MultiPart::BankMsb, voice.msb),
+		(MultiPart::BankLsb, voice.lsb),
+		(MultiPart::Program, voice.prg - 1),
+	].iter()
+		.map(|&(param, value)| {
+			xg_parameter_change(device, Address::multi_part(part, param), &[value])
+		})
+		.collect()
+}
+
+// The kinds of XG messages, by the high nibble of the device byte.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum XgMessage<'a> {
+	ParameterChange(Address, &'a [u8]),
+	// Checked for byte count and checksum.
+	BulkDump(Address, &'a [u8]),
+	DumpRequest(Address),
+	ParameterRequest(Address),
+}
+
+// Parses an XG message including F0 and F7, returning its device number
+// (0-15). None if it isn't an XG message at all.
+pub fn parse_xg(sysex: &[u8]) -> Option<Result<(u8, XgMessage<'_>), String>> {
+	let body = match *sysex {
+		[0xF0, ref body @ .., 0xF7] => body,
+		_ => return None,
+	};
+	let (dev, rest) = match *body {
+		[YAMAHA, dev, XG_MODEL, ref rest @ ..] => (dev, rest),
+		_ => return None,
+	};
+	let device = dev & 0x0F;
+	let address = |bytes: &[u8]| Address { high: bytes[0], mid: bytes[1], low: bytes[2] };
+	let message = match (dev & 0xF0, rest) {
+		(0x10, &[h, m, l, ref data @ ..]) if !data.is_empty() => {
+			Ok(XgMessage::ParameterChange(address(&[h, m, l]), data))
+		}
+		(0x00, &[bh, bl, h, m, l, ref data @ .., sum]) => {
+			let count = (bh as usize) << 7 | bl as usize;
+			let expected = checksum(&rest[..rest.len() - 1]);
+			if count != data.len() {
+				Err(format!(
+					"byte count is {}, but the dump has {} data bytes", count, data.len()
+				))
+			} else if sum != expected {
+				Err(format!("checksum is {:02X}, expected {:02X}", sum, expected))
+			} else {
+				Ok(XgMessage::BulkDump(address(&[h, m, l]), data))
+			}
+		}
+		(0x20, &[h, m, l]) => Ok(XgMessage::DumpRequest(address(&[h, m, l]))),
+		(0x30, &[h, m, l]) => Ok(XgMessage::ParameterRequest(address(&[h, m, l]))),
+		_ => Err(format!("malformed XG message: {}", Hex(sysex))),
+	};
+	Some(message.map(|m| (device, m)))
+}
+
+pub fn xg_dump_request(device: u8, address: Address) -> Vec<u8> {
+	let mut ret = vec![0xF0, YAMAHA, 0x20 | (device & 0x0F), XG_MODEL];
+	ret.extend_from_slice(&[address.high, address.mid, address.low, 0xF7]);
+	ret
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn checksum_of_known_messages() {
+		// Part 1 Part Mode = drum: count 00 01, address 08 00 07, data 01.
+		let dump = xg_bulk_dump(0, Address::multi_part(0, MultiPart::PartMode), &[0x01]);
+		assert_eq!(dump, [0xF0, 0x43, 0x00, 0x4C, 0x00, 0x01, 0x08, 0x00, 0x07, 0x01, 0x6F, 0xF7]);
+		// The GS Reset that every GS module documents.
+		assert_eq!(checksum(&[0x40, 0x00, 0x7F, 0x00]), 0x41);
+		assert_eq!(checksum(&[]), 0);
+	}
+
+	#[test]
+	fn parameter_change_round_trip() {
+		let address = Address::multi_part(2, MultiPart::Program);
+		let message = xg_parameter_change(5, address, &[0x30]);
+		let expected = XgMessage::ParameterChange(address, &[0x30][..]);
+		assert_eq!(parse_xg(&message), Some(Ok((5, expected))));
+	}
+
+	#[test]
+	fn bulk_dump_round_trip() {
+		let address = Address { high: 0x08, mid: 0x01, low: 0x00 };
+		let data: Vec<u8> = (0..0x90).map(|i| i as u8 & 0x7F).collect();
+		let message = xg_bulk_dump(0, address, &data);
+		// Byte counts above 127 take both count bytes.
+		assert_eq!(&message[4..6], &[0x01, 0x10]);
+		assert_eq!(parse_xg(&message), Some(Ok((0, XgMessage::BulkDump(address, &data[..])))));
+	}
+
+	#[test]
+	fn requests_round_trip() {
+		let address = Address::system(XG_SYSTEM_ON);
+		let message = xg_dump_request(3, address);
+		assert_eq!(parse_xg(&message), Some(Ok((3, XgMessage::DumpRequest(address)))));
+	}
+
+	#[test]
+	fn rejects_bad_dumps() {
+		let good = xg_bulk_dump(0, Address { high: 0x08, mid: 0x00, low: 0x00 }, &[0x00, 0x01, 0x02]);
+		let mut bad_checksum = good.clone();
+		bad_checksum[good.len() - 2] ^= 0x01;
+		assert!(matches!(parse_xg(&bad_checksum), Some(Err(ref e)) if e.contains("checksum")));
+		let mut bad_count = good.clone();
+		bad_count[5] = 0x04;
+		assert!(matches!(parse_xg(&bad_count), Some(Err(ref e)) if e.contains("byte count")));
+		// Without F7, it isn't a complete message.
+		assert_eq!(parse_xg(&good[..good.len() - 1]), None);
+		assert_eq!(parse_xg(&gs_reset(0)), None);
+	}
+}
@@ -7,24 +7,46 @@
 
 use std::fmt;
 use std::ops;
+use std::str::FromStr;
 
 pub struct Voice {
-    msb: u8, // 0-based
-    lsb: u8, // 0-based
-    prg: u8, // 1-based
-    name: &'static str,
+    pub msb: u8, // 0-based
+    pub lsb: u8, // 0-based
+    pub prg: u8, // 1-based
+    pub name: &'static str,
 }
 
-pub struct Voices(&'static [&'static Voice]);
+pub struct Voices(pub &'static [&'static Voice]);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VoiceKind {
+	Instrument,
+	Sfx,
+	Kit,
+}
 
 #[derive(Default)]
 pub struct VoiceCounts {
-	instruments: u16,
-	sfx: u16,
-	kits: u16,
+	pub instruments: u16,
+	pub sfx: u16,
+	pub kits: u16,
 }
 
 impl VoiceCounts {
+	// Counts by `Voice::kind`, for lists that are grouped by kind and so
+	// must agree with it.
+	pub fn by_kind<'a, I: IntoIterator<Item = &'a Voice>>(voices: I) -> VoiceCounts {
+		let mut ret: VoiceCounts = Default::default();
+		for it in voices {
+			match it.kind() {
+				VoiceKind::Kit => ret.kits += 1,
+				VoiceKind::Sfx => ret.sfx += 1,
+				VoiceKind::Instrument => ret.instruments += 1,
+			}
+		}
+		ret
+	}
+
 	pub fn total(&self) -> u16 {
 		self.instruments + self.sfx + self.kits
 	}
@@ -47,7 +69,68 @@ impl fmt::Display for VoiceCounts {
 	}
 }
 
+impl VoiceKind {
+	pub fn of_msb(msb: u8) -> VoiceKind {
+		match msb {
+			126 | 127 => VoiceKind::Kit,
+			64 => VoiceKind::Sfx,
+			_ => VoiceKind::Instrument,
+		}
+	}
+
+	// Stable lowercase identifier, for use in exported data.
+	pub fn id(self) -> &'static str {
+		match self {
+			VoiceKind::Instrument => "instrument",
+			VoiceKind::Sfx => "sfx",
+			VoiceKind::Kit => "kit",
+		}
+	}
+}
+
+impl FromStr for VoiceKind {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<VoiceKind, String> {
+		[VoiceKind::Instrument, VoiceKind::Sfx, VoiceKind::Kit].iter()
+			.find(|k| k.id() == s)
+			.cloned()
+			.ok_or_else(|| format!("unknown voice kind \"{}\", valid ones are instrument, sfx, kit", s))
+	}
+}
+
+impl Voice {
+	pub fn kind(&self) -> VoiceKind {
+		VoiceKind::of_msb(self.msb)
+	}
+
+	// Trailing spaces are a remnant of `melody.ini`, and shouldn't matter when
+	// comparing names from other sources.
+	pub fn name(&self) -> &'static str {
+		self.name.trim()
+	}
+}
+
 impl Voices {
+	// Turns voices created at runtime into a set that can be used alongside
+	// the static ones. The set lives until the program exits.
+	pub fn leak(voices: Vec<Voice>) -> &'static Voices {
+		let refs: Vec<&'static Voice> = voices.into_iter()
+			.map(|v| &*Box::leak(Box::new(v)))
+			.collect();
+		Box::leak(Box::new(Voices(Box::leak(refs.into_boxed_slice()))))
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = &'static Voice> {
+		self.0.iter().cloned()
+	}
+
+	pub fn find(&self, msb: u8, lsb: u8, prg: u8) -> Option<&'static Voice> {
+		self.iter().find(|v| v.msb == msb && v.lsb == lsb && v.prg == prg)
+	}
+
+	// Unlike `Voice::kind`, this counts the SFX kits of MSB 126 as
+	// instruments, to keep the totals that were always printed.
 	pub fn voicecount(&self) -> VoiceCounts {
 		let mut ret : VoiceCounts = Default::default();
 		for it in self.0.iter() {
@@ -68,6 +151,7 @@ impl fmt::Display for Voice {
 }
 
 impl fmt::Display for Voices {
+	#[allow(deprecated)]
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		for it in self.0.iter() {
 			try!(write!(f, "{}\n", it));
@@ -76,8 +160,33 @@ impl fmt::Display for Voices {
 	}
 }
 
+// Instrument categories of General MIDI, each covering 8 programs.
+pub const GM_CATEGORIES: [&str; 16] = [
+	"Piano",
+	"Chromatic Percussion",
+	"Organ",
+	"Guitar",
+	"Bass",
+	"Strings",
+	"Ensemble",
+	"Brass",
+	"Reed",
+	"Pipe",
+	"Synth Lead",
+	"Synth Pad",
+	"Synth Effects",
+	"Ethnic",
+	"Percussive",
+	"Sound Effects",
+];
+
+// Returns the General MIDI category of the given 1-based program number.
+pub fn gm_category(prg: u8) -> &'static str {
+	GM_CATEGORIES[((prg.max(1) - 1) / 8) as usize]
+}
+
 // General MIDI (1991)
-pub const GM: Voices = Voices(&[
+pub const GM: &Voices = &Voices(&[
 	&Voice{msb:   0, lsb:   0, prg:   1, name: "Grand Piano"},
 	&Voice{msb:   0, lsb:   0, prg:   2, name: "Bright Piano"},
 	&Voice{msb:   0, lsb:   0, prg:   3, name: "Electric Grand Piano"},
@@ -0,0 +1,547 @@
+/*
+ * Map of XG parameter addresses.
+ *
+ * The high byte of an address selects the block, the middle byte the part
+ * (Multi Part) or the note (Drum Setup), and the low byte the parameter
+ * within the block.
+ *
+ * Ranges and defaults are those of the XG specification. Parameters added by
+ * the MU100 and later modules are marked as such; MU80-class modules only
+ * implement XG Level 1.
+ */
+
+use std::fmt;
+use std::str::FromStr;
+
+use self::Format::*;
+use super::effects::EffectBlock;
+use super::models::Model;
+use super::sysex::Address;
+
+// How much of the XG parameter map a module implements.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+	Xg1,
+	Mu100,
+}
+
+impl fmt::Display for Level {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match *self {
+			Level::Xg1 => "XG Level 1",
+			Level::Mu100 => "MU100",
+		})
+	}
+}
+
+impl FromStr for Level {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Level, String> {
+		[Level::Xg1, Level::Mu100].iter()
+			.find(|l| l.to_string() == s)
+			.cloned()
+			.ok_or_else(|| format!("unknown XG level \"{}\", valid ones are XG Level 1, MU100", s))
+	}
+}
+
+// How the data bytes of a parameter are interpreted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+	Number,
+	// Centered on 64, as in -64..+63.
+	Signed,
+	Semitones,
+	Cents,
+	Decibels,
+	// 0 is random, 1-127 are L63..R63.
+	Pan,
+	Switch,
+	// 0-15 are channels 1-16, 127 is off.
+	Channel,
+	// 0-15 are parts 1-16, 127 is off.
+	Part,
+	MonoPoly,
+	KeyAssign,
+	PartMode,
+	// 0 is off.
+	Group,
+	Connection,
+	Note,
+	BankMsb,
+	BankLsb,
+	Program,
+	EffectType(EffectBlock),
+	// Parameters 1-16 of an effect block, which depend on the effect type.
+	EffectParam(EffectBlock, u8),
+	// 4 bits per byte, in tenths of the unit and centered on the given
+	// value.
+	Nibbles(i32, &'static str),
+	// Parameters without data, that trigger an action.
+	Trigger,
+}
+
+impl Format {
+	// `value` as shown on the module, as in "+2 semitones" or "L32".
+	pub fn display(self, value: u32) -> String {
+		let signed = value as i32 - 64;
+		match self {
+			Number | EffectParam(..) | Trigger | BankMsb | BankLsb => value.to_string(),
+			Program => format!("{} (program {})", value, value + 1),
+			Signed => format!("{:+}", signed),
+			Semitones => format!("{:+} semitones", signed),
+			Cents => format!("{:+} cents", signed),
+			Decibels => format!("{:+} dB", signed),
+			Pan => match value {
+				0 => "random".to_string(),
+				64 => "center".to_string(),
+				1..=63 => format!("L{}", 64 - value),
+				_ => format!("R{}", value - 64),
+			},
+			Switch => (if value == 0 { "off" } else { "on" }).to_string(),
+			Channel | Part => match value {
+				0..=31 if self == Part => format!("part {}", value + 1),
+				0..=15 => format!("channel {}", value + 1),
+				16..=31 => format!("channel B{}", value - 15),
+				127 => "off".to_string(),
+				_ => value.to_string(),
+			},
+			MonoPoly => (if value == 0 { "mono" } else { "poly" }).to_string(),
+			KeyAssign => match value {
+				0 => "single".to_string(),
+				1 => "multi".to_string(),
+				2 => "inst (for drum)".to_string(),
+				_ => value.to_string(),
+			},
+			PartMode => match value {
+				0 => "normal".to_string(),
+				1 => "drum".to_string(),
+				2 | 3 => format!("drum setup {}", value - 1),
+				_ => value.to_string(),
+			},
+			Group => if value == 0 { "off".to_string() } else { value.to_string() },
+			Connection => (if value == 0 { "insertion" } else { "system" }).to_string(),
+			Note => format!("{} ({})", NoteName(value as u8), value),
+			EffectType(block) => {
+				let (msb, lsb) = ((value >> 7) as u8, (value & 0x7F) as u8);
+				match block.find(msb, lsb) {
+					Some(t) => t.name.to_string(),
+					None => format!("{:02X} {:02X}", msb, lsb),
+				}
+			}
+			Nibbles(center, unit) => {
+				format!("{:+.1} {}", (value as i32 - center) as f64 / 10.0, unit)
+			}
+		}
+	}
+}
+
+pub struct Param {
+	// Low byte of the address.
+	pub offset: u8,
+	// Number of data bytes.
+	pub size: u8,
+	pub name: &'static str,
+	pub format: Format,
+	// Valid values, as combined by `value`.
+	pub min: u32,
+	pub max: u32,
+	// None if the default depends on the part, note or effect type.
+	pub default: Option<u32>,
+	pub level: Level,
+}
+
+const fn p(
+	offset: u8, size: u8, name: &'static str, format: Format,
+	min: u32, max: u32, default: Option<u32>,
+) -> Param {
+	Param { offset, size, name, format, min, max, default, level: Level::Xg1 }
+}
+
+const fn mu100(param: Param) -> Param {
+	Param { level: Level::Mu100, ..param }
+}
+
+// Shorthands for the most common ranges.
+const fn byte(offset: u8, name: &'static str, format: Format, default: Option<u32>) -> Param {
+	p(offset, 1, name, format, 0, 127, default)
+}
+
+const fn switch(offset: u8, name: &'static str, default: u32) -> Param {
+	p(offset, 1, name, Switch, 0, 1, Some(default))
+}
+
+// -24..+24 semitones.
+const fn pitch(offset: u8, name: &'static str, default: u32) -> Param {
+	p(offset, 1, name, Semitones, 0x28, 0x58, Some(default))
+}
+
+const fn effect_param(offset: u8, name: &'static str, block: EffectBlock, number: u8) -> Param {
+	byte(offset, name, EffectParam(block, number), None)
+}
+
+// The first ten variation parameters take two bytes each.
+const fn wide_effect_param(offset: u8, name: &'static str, number: u8) -> Param {
+	p(offset, 2, name, EffectParam(EffectBlock::Variation, number), 0, 0x3FFF, None)
+}
+
+// -12..+12 dB.
+const fn eq_gain(offset: u8, name: &'static str) -> Param {
+	mu100(p(offset, 1, name, Decibels, 0x34, 0x4C, Some(64)))
+}
+
+pub static SYSTEM: &[Param] = &[
+	p(0x00, 4, "Master Tune", Nibbles(1024, "cents"), 0, 2047, Some(1024)),
+	byte(0x04, "Master Volume", Number, Some(127)),
+	byte(0x05, "Master Attenuator", Number, Some(0)),
+	pitch(0x06, "Transpose", 64),
+	p(0x7D, 1, "Drum Setup Reset", Number, 0, 1, None),
+	p(0x7E, 1, "XG System On", Trigger, 0, 0, None),
+	p(0x7F, 1, "All Parameter Reset", Trigger, 0, 0, None),
+];
+
+pub static EFFECT: &[Param] = &[
+	p(0x00, 2, "Reverb Type", EffectType(EffectBlock::Reverb), 0, 0x3FFF, Some(0x01 << 7)),
+	effect_param(0x02, "Reverb Parameter 1", EffectBlock::Reverb, 1),
+	effect_param(0x03, "Reverb Parameter 2", EffectBlock::Reverb, 2),
+	effect_param(0x04, "Reverb Parameter 3", EffectBlock::Reverb, 3),
+	effect_param(0x05, "Reverb Parameter 4", EffectBlock::Reverb, 4),
+	effect_param(0x06, "Reverb Parameter 5", EffectBlock::Reverb, 5),
+	effect_param(0x07, "Reverb Parameter 6", EffectBlock::Reverb, 6),
+	effect_param(0x08, "Reverb Parameter 7", EffectBlock::Reverb, 7),
+	effect_param(0x09, "Reverb Parameter 8", EffectBlock::Reverb, 8),
+	effect_param(0x0A, "Reverb Parameter 9", EffectBlock::Reverb, 9),
+	effect_param(0x0B, "Reverb Parameter 10", EffectBlock::Reverb, 10),
+	byte(0x0C, "Reverb Return", Number, Some(64)),
+	p(0x0D, 1, "Reverb Pan", Pan, 1, 127, Some(64)),
+	effect_param(0x10, "Reverb Parameter 11", EffectBlock::Reverb, 11),
+	effect_param(0x11, "Reverb Parameter 12", EffectBlock::Reverb, 12),
+	effect_param(0x12, "Reverb Parameter 13", EffectBlock::Reverb, 13),
+	effect_param(0x13, "Reverb Parameter 14", EffectBlock::Reverb, 14),
+	effect_param(0x14, "Reverb Parameter 15", EffectBlock::Reverb, 15),
+	effect_param(0x15, "Reverb Parameter 16", EffectBlock::Reverb, 16),
+	p(0x20, 2, "Chorus Type", EffectType(EffectBlock::Chorus), 0, 0x3FFF, Some(0x41 << 7)),
+	effect_param(0x22, "Chorus Parameter 1", EffectBlock::Chorus, 1),
+	effect_param(0x23, "Chorus Parameter 2", EffectBlock::Chorus, 2),
+	effect_param(0x24, "Chorus Parameter 3", EffectBlock::Chorus, 3),
+	effect_param(0x25, "Chorus Parameter 4", EffectBlock::Chorus, 4),
+	effect_param(0x26, "Chorus Parameter 5", EffectBlock::Chorus, 5),
+	effect_param(0x27, "Chorus Parameter 6", EffectBlock::Chorus, 6),
+	effect_param(0x28, "Chorus Parameter 7", EffectBlock::Chorus, 7),
+	effect_param(0x29, "Chorus Parameter 8", EffectBlock::Chorus, 8),
+	effect_param(0x2A, "Chorus Parameter 9", EffectBlock::Chorus, 9),
+	effect_param(0x2B, "Chorus Parameter 10", EffectBlock::Chorus, 10),
+	byte(0x2C, "Chorus Return", Number, Some(64)),
+	p(0x2D, 1, "Chorus Pan", Pan, 1, 127, Some(64)),
+	byte(0x2E, "Send Chorus To Reverb", Number, Some(0)),
+	effect_param(0x30, "Chorus Parameter 11", EffectBlock::Chorus, 11),
+	effect_param(0x31, "Chorus Parameter 12", EffectBlock::Chorus, 12),
+	effect_param(0x32, "Chorus Parameter 13", EffectBlock::Chorus, 13),
+	effect_param(0x33, "Chorus Parameter 14", EffectBlock::Chorus, 14),
+	effect_param(0x34, "Chorus Parameter 15", EffectBlock::Chorus, 15),
+	effect_param(0x35, "Chorus Parameter 16", EffectBlock::Chorus, 16),
+	p(0x40, 2, "Variation Type", EffectType(EffectBlock::Variation), 0, 0x3FFF, Some(0x05 << 7)),
+	wide_effect_param(0x42, "Variation Parameter 1", 1),
+	wide_effect_param(0x44, "Variation Parameter 2", 2),
+	wide_effect_param(0x46, "Variation Parameter 3", 3),
+	wide_effect_param(0x48, "Variation Parameter 4", 4),
+	wide_effect_param(0x4A, "Variation Parameter 5", 5),
+	wide_effect_param(0x4C, "Variation Parameter 6", 6),
+	wide_effect_param(0x4E, "Variation Parameter 7", 7),
+	wide_effect_param(0x50, "Variation Parameter 8", 8),
+	wide_effect_param(0x52, "Variation Parameter 9", 9),
+	wide_effect_param(0x54, "Variation Parameter 10", 10),
+	byte(0x56, "Variation Return", Number, Some(64)),
+	p(0x57, 1, "Variation Pan", Pan, 1, 127, Some(64)),
+	byte(0x58, "Send Variation To Reverb", Number, Some(0)),
+	byte(0x59, "Send Variation To Chorus", Number, Some(0)),
+	p(0x5A, 1, "Variation Connection", Connection, 0, 1, Some(0)),
+	byte(0x5B, "Variation Part", Part, Some(127)),
+	byte(0x5C, "MW Variation Control Depth", Signed, Some(64)),
+	byte(0x5D, "Bend Variation Control Depth", Signed, Some(64)),
+	byte(0x5E, "CAT Variation Control Depth", Signed, Some(64)),
+	byte(0x5F, "AC1 Variation Control Depth", Signed, Some(64)),
+	byte(0x60, "AC2 Variation Control Depth", Signed, Some(64)),
+	effect_param(0x70, "Variation Parameter 11", EffectBlock::Variation, 11),
+	effect_param(0x71, "Variation Parameter 12", EffectBlock::Variation, 12),
+	effect_param(0x72, "Variation Parameter 13", EffectBlock::Variation, 13),
+	effect_param(0x73, "Variation Parameter 14", EffectBlock::Variation, 14),
+	effect_param(0x74, "Variation Parameter 15", EffectBlock::Variation, 15),
+	effect_param(0x75, "Variation Parameter 16", EffectBlock::Variation, 16),
+];
+
+// Defaults of None differ between part 10 (drums) and the other parts.
+pub static MULTI_PART: &[Param] = &[
+	p(0x00, 1, "Element Reserve", Number, 0, 32, None),
+	byte(0x01, "Bank Select MSB", BankMsb, None),
+	byte(0x02, "Bank Select LSB", BankLsb, Some(0)),
+	byte(0x03, "Program Number", Program, Some(0)),
+	byte(0x04, "Rcv Channel", Channel, None),
+	p(0x05, 1, "Mono/Poly Mode", MonoPoly, 0, 1, Some(1)),
+	p(0x06, 1, "Same Note Key Assign", KeyAssign, 0, 2, Some(1)),
+	p(0x07, 1, "Part Mode", PartMode, 0, 3, None),
+	pitch(0x08, "Note Shift", 64),
+	p(0x09, 2, "Detune", Nibbles(128, "Hz"), 0, 255, Some(128)),
+	byte(0x0B, "Volume", Number, Some(100)),
+	byte(0x0C, "Velocity Sense Depth", Number, Some(64)),
+	byte(0x0D, "Velocity Sense Offset", Number, Some(64)),
+	byte(0x0E, "Pan", Pan, Some(64)),
+	byte(0x0F, "Note Limit Low", Note, Some(0)),
+	byte(0x10, "Note Limit High", Note, Some(127)),
+	byte(0x11, "Dry Level", Number, Some(127)),
+	byte(0x12, "Chorus Send", Number, Some(0)),
+	byte(0x13, "Reverb Send", Number, Some(40)),
+	byte(0x14, "Variation Send", Number, Some(0)),
+	byte(0x15, "Vibrato Rate", Signed, Some(64)),
+	byte(0x16, "Vibrato Depth", Signed, Some(64)),
+	byte(0x17, "Vibrato Delay", Signed, Some(64)),
+	byte(0x18, "Filter Cutoff Frequency", Signed, Some(64)),
+	byte(0x19, "Filter Resonance", Signed, Some(64)),
+	byte(0x1A, "EG Attack Time", Signed, Some(64)),
+	byte(0x1B, "EG Decay Time", Signed, Some(64)),
+	byte(0x1C, "EG Release Time", Signed, Some(64)),
+	pitch(0x1D, "MW Pitch Control", 64),
+	byte(0x1E, "MW Filter Control", Signed, Some(64)),
+	byte(0x1F, "MW Amplitude Control", Signed, Some(64)),
+	byte(0x20, "MW LFO PMod Depth", Number, Some(10)),
+	byte(0x21, "MW LFO FMod Depth", Number, Some(0)),
+	byte(0x22, "MW LFO AMod Depth", Number, Some(0)),
+	pitch(0x23, "Bend Pitch Control", 0x42),
+	byte(0x24, "Bend Filter Control", Signed, Some(64)),
+	byte(0x25, "Bend Amplitude Control", Signed, Some(64)),
+	byte(0x26, "Bend LFO PMod Depth", Signed, Some(64)),
+	byte(0x27, "Bend LFO FMod Depth", Signed, Some(64)),
+	byte(0x28, "Bend LFO AMod Depth", Signed, Some(64)),
+	switch(0x30, "Rcv Pitch Bend", 1),
+	switch(0x31, "Rcv Channel After Touch", 1),
+	switch(0x32, "Rcv Program Change", 1),
+	switch(0x33, "Rcv Control Change", 1),
+	switch(0x34, "Rcv Poly After Touch", 1),
+	switch(0x35, "Rcv Note Message", 1),
+	switch(0x36, "Rcv RPN", 1),
+	switch(0x37, "Rcv NRPN", 1),
+	switch(0x38, "Rcv Modulation", 1),
+	switch(0x39, "Rcv Volume", 1),
+	switch(0x3A, "Rcv Pan", 1),
+	switch(0x3B, "Rcv Expression", 1),
+	switch(0x3C, "Rcv Hold 1", 1),
+	switch(0x3D, "Rcv Portamento", 1),
+	switch(0x3E, "Rcv Sostenuto", 1),
+	switch(0x3F, "Rcv Soft Pedal", 1),
+	switch(0x40, "Rcv Bank Select", 1),
+	byte(0x41, "Scale Tuning C", Cents, Some(64)),
+	byte(0x42, "Scale Tuning C#", Cents, Some(64)),
+	byte(0x43, "Scale Tuning D", Cents, Some(64)),
+	byte(0x44, "Scale Tuning D#", Cents, Some(64)),
+	byte(0x45, "Scale Tuning E", Cents, Some(64)),
+	byte(0x46, "Scale Tuning F", Cents, Some(64)),
+	byte(0x47, "Scale Tuning F#", Cents, Some(64)),
+	byte(0x48, "Scale Tuning G", Cents, Some(64)),
+	byte(0x49, "Scale Tuning G#", Cents, Some(64)),
+	byte(0x4A, "Scale Tuning A", Cents, Some(64)),
+	byte(0x4B, "Scale Tuning A#", Cents, Some(64)),
+	byte(0x4C, "Scale Tuning B", Cents, Some(64)),
+	pitch(0x4D, "CAT Pitch Control", 64),
+	byte(0x4E, "CAT Filter Control", Signed, Some(64)),
+	byte(0x4F, "CAT Amplitude Control", Signed, Some(64)),
+	byte(0x50, "CAT LFO PMod Depth", Number, Some(0)),
+	byte(0x51, "CAT LFO FMod Depth", Number, Some(0)),
+	byte(0x52, "CAT LFO AMod Depth", Number, Some(0)),
+	pitch(0x53, "PAT Pitch Control", 64),
+	byte(0x54, "PAT Filter Control", Signed, Some(64)),
+	byte(0x55, "PAT Amplitude Control", Signed, Some(64)),
+	byte(0x56, "PAT LFO PMod Depth", Number, Some(0)),
+	byte(0x57, "PAT LFO FMod Depth", Number, Some(0)),
+	byte(0x58, "PAT LFO AMod Depth", Number, Some(0)),
+	p(0x59, 1, "AC1 Controller Number", Number, 0, 95, Some(16)),
+	pitch(0x5A, "AC1 Pitch Control", 64),
+	byte(0x5B, "AC1 Filter Control", Signed, Some(64)),
+	byte(0x5C, "AC1 Amplitude Control", Signed, Some(64)),
+	byte(0x5D, "AC1 LFO PMod Depth", Number, Some(0)),
+	byte(0x5E, "AC1 LFO FMod Depth", Number, Some(0)),
+	byte(0x5F, "AC1 LFO AMod Depth", Number, Some(0)),
+	p(0x60, 1, "AC2 Controller Number", Number, 0, 95, Some(17)),
+	pitch(0x61, "AC2 Pitch Control", 64),
+	byte(0x62, "AC2 Filter Control", Signed, Some(64)),
+	byte(0x63, "AC2 Amplitude Control", Signed, Some(64)),
+	byte(0x64, "AC2 LFO PMod Depth", Number, Some(0)),
+	byte(0x65, "AC2 LFO FMod Depth", Number, Some(0)),
+	byte(0x66, "AC2 LFO AMod Depth", Number, Some(0)),
+	switch(0x67, "Portamento Switch", 0),
+	byte(0x68, "Portamento Time", Number, Some(0)),
+	byte(0x69, "Pitch EG Initial Level", Signed, Some(64)),
+	byte(0x6A, "Pitch EG Attack Time", Signed, Some(64)),
+	byte(0x6B, "Pitch EG Release Level", Signed, Some(64)),
+	byte(0x6C, "Pitch EG Release Time", Signed, Some(64)),
+	p(0x6D, 1, "Velocity Limit Low", Number, 1, 127, Some(1)),
+	p(0x6E, 1, "Velocity Limit High", Number, 1, 127, Some(127)),
+	eq_gain(0x72, "EQ Bass Gain"),
+	eq_gain(0x73, "EQ Treble Gain"),
+	mu100(p(0x76, 1, "EQ Bass Frequency", Number, 0x04, 0x28, Some(0x0C))),
+	mu100(p(0x77, 1, "EQ Treble Frequency", Number, 0x1C, 0x3A, Some(0x36))),
+];
+
+// Defaults of None depend on the drum instrument.
+pub static DRUM_SETUP: &[Param] = &[
+	byte(0x00, "Pitch Coarse", Semitones, Some(64)),
+	byte(0x01, "Pitch Fine", Cents, Some(64)),
+	byte(0x02, "Level", Number, None),
+	byte(0x03, "Alternate Group", Group, None),
+	byte(0x04, "Pan", Pan, None),
+	byte(0x05, "Reverb Send", Number, None),
+	byte(0x06, "Chorus Send", Number, None),
+	byte(0x07, "Variation Send", Number, Some(127)),
+	p(0x08, 1, "Key Assign", KeyAssign, 0, 1, Some(0)),
+	p(0x09, 1, "Rcv Note Off", Switch, 0, 1, None),
+	switch(0x0A, "Rcv Note On", 1),
+	byte(0x0B, "Filter Cutoff Frequency", Signed, Some(64)),
+	byte(0x0C, "Filter Resonance", Signed, Some(64)),
+	byte(0x0D, "EG Attack Rate", Signed, Some(64)),
+	byte(0x0E, "EG Decay 1 Rate", Signed, Some(64)),
+	byte(0x0F, "EG Decay 2 Rate", Signed, Some(64)),
+	eq_gain(0x20, "EQ Bass Gain"),
+	eq_gain(0x21, "EQ Treble Gain"),
+	mu100(p(0x24, 1, "EQ Bass Frequency", Number, 0x04, 0x28, Some(0x0C))),
+	mu100(p(0x25, 1, "EQ Treble Frequency", Number, 0x1C, 0x3A, Some(0x36))),
+];
+
+impl Param {
+	// Combines the data bytes of the parameter, most significant first.
+	pub fn value(&self, data: &[u8]) -> u32 {
+		match self.format {
+			Nibbles(..) => data.iter().fold(0, |acc, &b| (acc << 4) | (b & 0x0F) as u32),
+			_ => data.iter().fold(0, |acc, &b| (acc << 7) | (b & 0x7F) as u32),
+		}
+	}
+
+	// The inverse of `value`.
+	pub fn bytes(&self, value: u32) -> Vec<u8> {
+		let (bits, mask) = match self.format {
+			Nibbles(..) => (4, 0x0F),
+			_ => (7, 0x7F),
+		};
+		(0..self.size as u32).rev().map(|i| ((value >> (bits * i)) & mask) as u8).collect()
+	}
+
+	pub fn available_on(&self, model: &Model) -> bool {
+		model.xg_level.is_some_and(|level| level >= self.level)
+	}
+
+	// As in "-24 semitones to +24 semitones".
+	pub fn range(&self) -> String {
+		match self.format {
+			EffectType(block) => format!("{} types", block.types().count()),
+			format => format!("{} to {}", format.display(self.min), format.display(self.max)),
+		}
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Block {
+	System,
+	Effect,
+	// 0-based part.
+	MultiPart(u8),
+	// 0-based drum setup and note.
+	DrumSetup(u8, u8),
+}
+
+// Modules with two MIDI inputs, like the MU80, have 32 parts. Parts 17-32
+// receive on the second input, shown as channels B1-B16.
+pub const MULTI_PARTS: u8 = 32;
+
+// Number of drum setups of XG Level 1 modules.
+pub const DRUM_SETUPS: u8 = 2;
+
+// One block of each kind, standing for all parts and notes.
+pub const BLOCKS: [Block; 4] = [
+	Block::System,
+	Block::Effect,
+	Block::MultiPart(0),
+	Block::DrumSetup(0, 0),
+];
+
+impl Block {
+	pub fn of(address: Address) -> Option<Block> {
+		match (address.high, address.mid) {
+			(0x00, 0x00) => Some(Block::System),
+			(0x02, 0x01) => Some(Block::Effect),
+			(0x08, part) if part < MULTI_PARTS => Some(Block::MultiPart(part)),
+			(high, note) if high & 0xF0 == 0x30 && high & 0x0F < DRUM_SETUPS => {
+				Some(Block::DrumSetup(high & 0x0F, note))
+			}
+			_ => None,
+		}
+	}
+
+	pub fn params(self) -> &'static [Param] {
+		match self {
+			Block::System => SYSTEM,
+			Block::Effect => EFFECT,
+			Block::MultiPart(_) => MULTI_PART,
+			Block::DrumSetup(..) => DRUM_SETUP,
+		}
+	}
+
+	pub fn param(self, offset: u8) -> Option<&'static Param> {
+		self.params().iter().find(|p| p.offset == offset)
+	}
+
+	pub fn kind(self) -> &'static str {
+		match self {
+			Block::System => "System",
+			Block::Effect => "Effect",
+			Block::MultiPart(_) => "Multi Part",
+			Block::DrumSetup(..) => "Drum Setup",
+		}
+	}
+
+	// High and middle bytes of the addresses of blocks of this kind, as in
+	// "08 pp" for the multi parts.
+	pub fn pattern(self) -> &'static str {
+		match self {
+			Block::System => "00 00",
+			Block::Effect => "02 01",
+			Block::MultiPart(_) => "08 pp",
+			Block::DrumSetup(..) => "3n rr",
+		}
+	}
+}
+
+// The parameter at `address`, which must be the parameter's first byte.
+pub fn find(address: Address) -> Option<(Block, &'static Param)> {
+	let block = Block::of(address)?;
+	Some((block, block.param(address.low)?))
+}
+
+// Parameters called `name`, ignoring case, in all blocks. Names like "Pan"
+// exist in several blocks.
+pub fn find_by_name(name: &str) -> Vec<(Block, &'static Param)> {
+	BLOCKS.iter()
+		.flat_map(|&block| block.params().iter().map(move |param| (block, param)))
+		.filter(|(_, param)| param.name.eq_ignore_ascii_case(name))
+		.collect()
+}
+
+// Yamaha numbers notes so that middle C (60) is C3.
+pub struct NoteName(pub u8);
+
+impl fmt::Display for NoteName {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+		write!(f, "{}{}", NAMES[(self.0 % 12) as usize], (self.0 / 12) as i32 - 2)
+	}
+}
+
+impl fmt::Display for Block {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Block::System => f.write_str("System"),
+			Block::Effect => f.write_str("Effect"),
+			Block::MultiPart(part) => write!(f, "Multi Part {}", part + 1),
+			Block::DrumSetup(setup, note) => {
+				write!(f, "Drum Setup {}, note {} ({})", setup + 1, NoteName(note), note)
+			}
+		}
+	}
+}